@@ -26,6 +26,9 @@ impl AppState {
         }
 
         let db = Database::open(&db_path)?;
+        if let Err(e) = db.run_maintenance() {
+            tracing::warn!("Startup maintenance failed: {}", e);
+        }
         let chest = HallChest::new()?;
 
         Ok(Self {
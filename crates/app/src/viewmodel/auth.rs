@@ -2,11 +2,8 @@
 
 use std::sync::Arc;
 
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
-};
-use exom_core::{Session, User};
+use exom_core::storage::HashParams;
+use exom_core::{is_reserved_username, Session, User};
 use slint::ComponentHandle;
 
 use crate::state::AppState;
@@ -23,12 +20,28 @@ pub fn setup_auth_bindings(window: &MainWindow, state: Arc<AppState>) {
         let db = state_login.db.lock().unwrap();
         let users = db.users();
 
-        // Find user
-        let user = match users.find_by_username(&username) {
+        // Refuse locked accounts before checking the password
+        if let Ok(Some(locked_until)) = users.is_locked(&username) {
+            if let Some(w) = window_weak.upgrade() {
+                let until = locked_until.with_timezone(&chrono::Local).format("%H:%M");
+                w.set_auth_error(format!("Account locked until {}", until).into());
+            }
+            return;
+        }
+
+        // Verify password, upgrading outdated hashes
+        let params = HashParams::default();
+        let user = match users.verify_and_maybe_rehash(&username, &password, &params) {
             Ok(Some(u)) => u,
             Ok(None) => {
+                let locked = users.record_failed_login(&username).ok().flatten();
                 if let Some(w) = window_weak.upgrade() {
-                    w.set_auth_error("User not found".into());
+                    let message = if locked.is_some() {
+                        "Too many failed attempts, account locked"
+                    } else {
+                        "Invalid username or password"
+                    };
+                    w.set_auth_error(message.into());
                 }
                 return;
             }
@@ -40,29 +53,8 @@ pub fn setup_auth_bindings(window: &MainWindow, state: Arc<AppState>) {
             }
         };
 
-        // Verify password
-        let argon2 = Argon2::default();
-        let parsed_hash = match PasswordHash::new(&user.password_hash) {
-            Ok(h) => h,
-            Err(_) => {
-                if let Some(w) = window_weak.upgrade() {
-                    w.set_auth_error("Invalid stored password".into());
-                }
-                return;
-            }
-        };
-
-        if argon2
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .is_err()
-        {
-            if let Some(w) = window_weak.upgrade() {
-                w.set_auth_error("Invalid password".into());
-            }
-            return;
-        }
-
-        // Update last login
+        // Reset failed attempts and update last login
+        let _ = users.clear_failed_logins(user.id);
         let _ = users.update_last_login(user.id);
 
         // Create session
@@ -101,6 +93,13 @@ pub fn setup_auth_bindings(window: &MainWindow, state: Arc<AppState>) {
             return;
         }
 
+        if is_reserved_username(&username) {
+            if let Some(w) = window_weak.upgrade() {
+                w.set_auth_error("That username is reserved".into());
+            }
+            return;
+        }
+
         if password.len() < 6 {
             if let Some(w) = window_weak.upgrade() {
                 w.set_auth_error("Password must be at least 6 characters".into());
@@ -109,10 +108,8 @@ pub fn setup_auth_bindings(window: &MainWindow, state: Arc<AppState>) {
         }
 
         // Hash password
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = match argon2.hash_password(password.as_bytes(), &salt) {
-            Ok(h) => h.to_string(),
+        let password_hash = match HashParams::default().hash_password(&password) {
+            Ok(h) => h,
             Err(_) => {
                 if let Some(w) = window_weak.upgrade() {
                     w.set_auth_error("Failed to hash password".into());
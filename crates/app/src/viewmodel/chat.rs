@@ -3,7 +3,7 @@
 use std::sync::Arc;
 
 use chrono::{DateTime, Duration, Utc};
-use exom_core::Message;
+use exom_core::{Message, SETTING_SLOW_MODE_SECS};
 use slint::{ComponentHandle, ModelRc, VecModel};
 
 use crate::state::AppState;
@@ -60,12 +60,20 @@ pub fn setup_chat_bindings(window: &MainWindow, state: Arc<AppState>) {
                 is_edited: m.is_edited,
                 is_group_start,
                 is_host,
+                sender_color: super::hex_color(&m.sender_color),
             });
 
             prev_sender = Some(m.sender_username.clone());
             prev_timestamp = Some(m.timestamp);
         }
 
+        // Everything on screen now counts as read
+        if let Some(user_id) = state_load.current_user_id() {
+            if let Ok(latest) = db.messages().latest_sequence(hall_id) {
+                let _ = db.messages().mark_read(user_id, hall_id, latest);
+            }
+        }
+
         drop(db);
 
         if let Some(w) = window_weak.upgrade() {
@@ -96,11 +104,34 @@ pub fn setup_chat_bindings(window: &MainWindow, state: Arc<AppState>) {
         let message = Message::new(hall_id, user_id, content);
 
         let db = state_send.db.lock().unwrap();
+        let role = match db.halls().get_user_role(user_id, hall_id) {
+            Ok(Some(r)) => r,
+            _ => return,
+        };
+        let slow_mode_secs = db
+            .halls()
+            .get_setting::<u32>(hall_id, SETTING_SLOW_MODE_SECS)
+            .ok()
+            .flatten()
+            .unwrap_or(0);
+        if let Err(e) = db
+            .messages()
+            .can_send_now(hall_id, user_id, role, slow_mode_secs)
+        {
+            if let Some(w) = window_weak.upgrade() {
+                w.set_chat_error(e.to_string().into());
+            }
+            return;
+        }
         if db.messages().create(&message).is_err() {
             return;
         }
         drop(db);
 
+        if let Some(w) = window_weak.upgrade() {
+            w.set_chat_error("".into());
+        }
+
         // Reload messages
         if let Some(w) = window_weak.upgrade() {
             w.invoke_load_messages();
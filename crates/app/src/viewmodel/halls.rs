@@ -2,7 +2,8 @@
 
 use std::sync::Arc;
 
-use exom_core::{Hall, HallRole, HostElectionResult, HostingState, Invite, Membership};
+use exom_core::storage::{HallStore, InviteStore};
+use exom_core::{Error, Hall, HallRole, HostElectionResult, HostingState, Invite, Membership};
 use rand::Rng;
 use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
 
@@ -36,11 +37,15 @@ pub fn setup_hall_bindings(window: &MainWindow, state: Arc<AppState>) {
                     .ok()
                     .flatten()
                     .unwrap_or(HallRole::HallFellow);
+                let member_count = db.halls().member_count(h.id).unwrap_or(0);
+                let online_count = db.halls().online_count(h.id).unwrap_or(0);
 
                 HallItem {
                     id: h.id.to_string().into(),
                     name: h.name.clone().into(),
                     role: role.short_name().into(),
+                    member_count: member_count as i32,
+                    online_count: online_count as i32,
                 }
             })
             .collect();
@@ -76,13 +81,14 @@ pub fn setup_hall_bindings(window: &MainWindow, state: Arc<AppState>) {
 
         let db = state_create.db.lock().unwrap();
 
-        if db.halls().create(&hall).is_err() {
-            return;
-        }
-
-        // Add creator as Builder
+        // Create hall and add creator as Builder atomically
         let membership = Membership::new(user_id, hall_id, HallRole::HallBuilder);
-        if db.halls().add_member(&membership).is_err() {
+        let created = db.transaction(|tx| {
+            let halls = HallStore::new(tx);
+            halls.create(&hall)?;
+            halls.add_member(&membership)
+        });
+        if created.is_err() {
             return;
         }
 
@@ -184,6 +190,8 @@ pub fn setup_hall_bindings(window: &MainWindow, state: Arc<AppState>) {
             w.set_current_hall_name(hall.name.into());
             w.set_current_host_name(host_name);
             w.set_current_user_role(role.display_name().into());
+            // A slow-mode notice belongs to the Hall it was raised in
+            w.set_chat_error("".into());
             w.invoke_load_messages();
             w.invoke_load_members();
         }
@@ -226,18 +234,25 @@ pub fn setup_hall_bindings(window: &MainWindow, state: Arc<AppState>) {
             _ => return,
         };
 
-        // Add membership
+        // Consume an invite use and add membership together
         let membership = Membership::new(user_id, invite.hall_id, invite.role);
-        if db.halls().add_member(&membership).is_err() {
+        let joined = db.transaction(|tx| {
+            InviteStore::new(tx).redeem(&token)?;
+            HallStore::new(tx).add_member(&membership)
+        });
+        if let Err(e) = joined {
             if let Some(w) = window_weak.upgrade() {
-                w.set_hall_error("Failed to join Hall".into());
+                let message = match e {
+                    Error::Banned => "You are banned from this Hall",
+                    Error::InviteExpired => "Invite has expired",
+                    Error::InviteExhausted => "Invite has no uses left",
+                    _ => "Failed to join Hall",
+                };
+                w.set_hall_error(message.into());
             }
             return;
         }
 
-        // Increment invite use count
-        let _ = db.invites().increment_use_count(invite.id);
-
         // Init chest if role allows
         if invite.role >= HallRole::HallAgent {
             let chest = state_join.chest.lock().unwrap();
@@ -2,7 +2,7 @@
 
 use std::sync::Arc;
 
-use exom_core::{HallAction, HallRole, PermissionMatrix};
+use exom_core::{HallAction, HallRole, ModerationAction, PermissionMatrix};
 use slint::{ComponentHandle, ModelRc, VecModel};
 
 use crate::state::AppState;
@@ -37,6 +37,7 @@ pub fn setup_member_bindings(window: &MainWindow, state: Arc<AppState>) {
                 is_online: m.is_online,
                 is_host: m.is_host,
                 is_you: current_user_id == Some(m.user_id),
+                color: super::hex_color(&m.color),
             })
             .collect();
 
@@ -79,14 +80,20 @@ pub fn setup_member_bindings(window: &MainWindow, state: Arc<AppState>) {
             _ => return,
         };
 
-        // Get target's current role
+        // Get target's effective role (for permission checks) and the
+        // stored base role, which role changes step from so a temporary
+        // grant is never written back as permanent
         let target_role = match db.halls().get_user_role(target_id, hall_id) {
             Ok(Some(r)) => r,
             _ => return,
         };
+        let base_role = match db.halls().base_role(target_id, hall_id) {
+            Ok(Some(r)) => r,
+            _ => return,
+        };
 
         // Calculate new role (one level up)
-        let new_role = match target_role {
+        let new_role = match base_role {
             HallRole::HallFellow => HallRole::HallAgent,
             HallRole::HallAgent => HallRole::HallModerator,
             HallRole::HallModerator => HallRole::HallPrefect,
@@ -98,7 +105,16 @@ pub fn setup_member_bindings(window: &MainWindow, state: Arc<AppState>) {
             return;
         }
 
-        let _ = db.halls().update_role(target_id, hall_id, new_role);
+        if db.halls().update_role(target_id, hall_id, new_role).is_ok() {
+            let change = format!("{} to {}", base_role.short_name(), new_role.short_name());
+            let _ = db.halls().log_moderation(
+                hall_id,
+                user_id,
+                target_id,
+                ModerationAction::Promote,
+                Some(&change),
+            );
+        }
         drop(db);
 
         // Init chest if promoted to Agent
@@ -141,26 +157,46 @@ pub fn setup_member_bindings(window: &MainWindow, state: Arc<AppState>) {
             _ => return,
         };
 
-        // Get target's current role
+        // Get target's effective role (for permission checks) and the
+        // stored base role, which role changes step from so a temporary
+        // grant is never written back as permanent
         let target_role = match db.halls().get_user_role(target_id, hall_id) {
             Ok(Some(r)) => r,
             _ => return,
         };
+        let base_role = match db.halls().base_role(target_id, hall_id) {
+            Ok(Some(r)) => r,
+            _ => return,
+        };
 
         // Calculate new role (one level down)
-        let new_role = match target_role {
+        let new_role = match base_role {
             HallRole::HallPrefect => HallRole::HallModerator,
             HallRole::HallModerator => HallRole::HallAgent,
             HallRole::HallAgent => HallRole::HallFellow,
             HallRole::HallFellow | HallRole::HallBuilder => return, // Can't demote further
         };
 
-        // Check permission
-        if !PermissionMatrix::can_change_role(actor_role, target_role, new_role) {
+        // Members may always step themselves down; step_down checks this
+        // against the base role
+        let changed = if target_id == user_id {
+            db.halls().step_down(user_id, hall_id, new_role)
+        } else if PermissionMatrix::can_change_role(actor_role, target_role, new_role) {
+            db.halls().update_role(target_id, hall_id, new_role)
+        } else {
             return;
-        }
+        };
 
-        let _ = db.halls().update_role(target_id, hall_id, new_role);
+        if changed.is_ok() {
+            let change = format!("{} to {}", base_role.short_name(), new_role.short_name());
+            let _ = db.halls().log_moderation(
+                hall_id,
+                user_id,
+                target_id,
+                ModerationAction::Demote,
+                Some(&change),
+            );
+        }
         drop(db);
 
         if let Some(w) = window_weak.upgrade() {
@@ -205,7 +241,7 @@ pub fn setup_member_bindings(window: &MainWindow, state: Arc<AppState>) {
             return;
         }
 
-        let _ = db.halls().remove_member(target_id, hall_id);
+        let _ = db.halls().kick_member(hall_id, target_id, user_id, None);
         drop(db);
 
         if let Some(w) = window_weak.upgrade() {
@@ -17,3 +17,9 @@ pub fn setup_bindings(window: &MainWindow, state: AppState) {
     chat::setup_chat_bindings(window, state.clone());
     members::setup_member_bindings(window, state.clone());
 }
+
+/// Convert a `#rrggbb` color from the core models into a Slint color
+fn hex_color(hex: &str) -> slint::Color {
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("00"), 16).unwrap_or(0);
+    slint::Color::from_rgb_u8(channel(1), channel(3), channel(5))
+}
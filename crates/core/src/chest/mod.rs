@@ -4,7 +4,7 @@
 //! Sync is NOT implemented yet, but the interface is designed for future sync.
 
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use directories::ProjectDirs;
 use tracing::instrument;
@@ -12,6 +12,12 @@ use uuid::Uuid;
 
 use crate::error::{Error, Result};
 use crate::models::HallRole;
+use crate::permissions::require_can_write_chest_path;
+
+/// Name of the metadata file at the root of every Hall chest
+///
+/// It holds settings such as the quota, so members can never write it.
+pub const HALL_META_FILE: &str = ".hall_meta.json";
 
 /// Manages local Hall Chest folders
 pub struct HallChest {
@@ -93,9 +99,10 @@ impl HallChest {
     ) -> Result<PathBuf> {
         // Fellows don't get chest access
         if role < HallRole::HallAgent {
-            return Err(Error::PermissionDenied(
-                "Fellows do not have Hall Chest access".into(),
-            ));
+            return Err(Error::RoleTooLow {
+                required: HallRole::HallAgent,
+                actual: role,
+            });
         }
 
         let hall_path = self.hall_path(hall_id);
@@ -110,7 +117,7 @@ impl HallChest {
         }
 
         // Create a metadata file
-        let meta_path = hall_path.join(".hall_meta.json");
+        let meta_path = self.meta_path(hall_id);
         if !meta_path.exists() {
             let meta = serde_json::json!({
                 "hall_id": hall_id.to_string(),
@@ -124,6 +131,96 @@ impl HallChest {
         Ok(hall_path)
     }
 
+    /// Set the Hall's chest quota in bytes, or remove it with `None`
+    ///
+    /// The quota is kept in the chest's metadata file, so the chest must
+    /// have been initialized.
+    #[instrument(skip(self))]
+    pub fn set_quota(&self, hall_id: Uuid, bytes: Option<u64>) -> Result<()> {
+        let meta_path = self.meta_path(hall_id);
+        let mut meta = self.read_meta(hall_id)?;
+        match bytes {
+            Some(bytes) => meta["quota_bytes"] = bytes.into(),
+            None => {
+                if let Some(obj) = meta.as_object_mut() {
+                    obj.remove("quota_bytes");
+                }
+            }
+        }
+        fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)?;
+        Ok(())
+    }
+
+    /// Get the Hall's chest quota in bytes, if one is set
+    pub fn quota(&self, hall_id: Uuid) -> Result<Option<u64>> {
+        Ok(self.read_meta(hall_id)?["quota_bytes"].as_u64())
+    }
+
+    /// Write a file into the Hall chest, enforcing folder rules and the quota
+    ///
+    /// `subpath` is relative to the chest root and may not contain `..` or
+    /// name the chest's metadata file. Which folders the member `actor_id`
+    /// holding `role` may write to is decided by
+    /// [`PermissionMatrix::can_write_chest_path`]. Overwriting a file only
+    /// counts the difference in size. Nothing is written if the result
+    /// would exceed the quota.
+    ///
+    /// [`PermissionMatrix::can_write_chest_path`]: crate::permissions::PermissionMatrix::can_write_chest_path
+    #[instrument(skip(self, contents), fields(len = contents.len()))]
+    pub fn write_file(
+        &self,
+        hall_id: Uuid,
+        actor_id: Uuid,
+        role: HallRole,
+        subpath: &str,
+        contents: &[u8],
+    ) -> Result<()> {
+        let relative = Path::new(subpath);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(Error::InvalidOperation(format!(
+                "Invalid chest path: {}",
+                subpath
+            )));
+        }
+        if relative.starts_with(HALL_META_FILE) {
+            return Err(Error::PermissionDenied(format!(
+                "{} is reserved",
+                HALL_META_FILE
+            )));
+        }
+        require_can_write_chest_path(role, actor_id, subpath)?;
+
+        let path = self.hall_path(hall_id).join(relative);
+        if let Some(quota) = self.quota(hall_id)? {
+            let existing = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            let required = self.chest_size(hall_id)? - existing + contents.len() as u64;
+            if required > quota {
+                return Err(Error::QuotaExceeded { quota, required });
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        Ok(())
+    }
+
+    fn meta_path(&self, hall_id: Uuid) -> PathBuf {
+        self.hall_path(hall_id).join(HALL_META_FILE)
+    }
+
+    fn read_meta(&self, hall_id: Uuid) -> Result<serde_json::Value> {
+        let meta_path = self.meta_path(hall_id);
+        if !meta_path.exists() {
+            return Err(Error::NotFound(format!("Hall chest {}", hall_id)));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(meta_path)?)?)
+    }
+
     /// Get path for a Hall's chest
     pub fn hall_path(&self, hall_id: Uuid) -> PathBuf {
         self.base_path.join(hall_id.to_string())
@@ -264,4 +361,125 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_write_file_respects_quota() {
+        let dir = tempdir().unwrap();
+        let chest = HallChest::with_base_path(dir.path().to_path_buf()).unwrap();
+
+        let hall_id = Uuid::new_v4();
+        let path = chest
+            .init_hall_chest(hall_id, "Test Hall", HallRole::HallAgent)
+            .unwrap();
+        let actor = Uuid::new_v4();
+        let used = chest.chest_size(hall_id).unwrap();
+        chest.set_quota(hall_id, Some(used + 100)).unwrap();
+        assert_eq!(chest.quota(hall_id).unwrap(), Some(used + 100));
+
+        chest
+            .write_file(
+                hall_id,
+                actor,
+                HallRole::HallAgent,
+                "shared/notes.txt",
+                &[b'a'; 50],
+            )
+            .unwrap();
+        assert_eq!(fs::read(path.join("shared/notes.txt")).unwrap().len(), 50);
+
+        let result = chest.write_file(
+            hall_id,
+            actor,
+            HallRole::HallAgent,
+            "shared/big.bin",
+            &[0; 200],
+        );
+        assert!(matches!(result, Err(Error::QuotaExceeded { .. })));
+        assert!(!path.join("shared/big.bin").exists());
+
+        // Removing the quota lifts the limit
+        chest.set_quota(hall_id, None).unwrap();
+        chest
+            .write_file(
+                hall_id,
+                actor,
+                HallRole::HallAgent,
+                "shared/big.bin",
+                &[0; 200],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_write_file_rejects_escaping_paths() {
+        let dir = tempdir().unwrap();
+        let chest = HallChest::with_base_path(dir.path().to_path_buf()).unwrap();
+
+        let hall_id = Uuid::new_v4();
+        chest
+            .init_hall_chest(hall_id, "Test Hall", HallRole::HallAgent)
+            .unwrap();
+
+        let actor = Uuid::new_v4();
+        for subpath in ["../outside.txt", "/etc/passwd", "shared/../../x"] {
+            assert!(matches!(
+                chest.write_file(hall_id, actor, HallRole::HallBuilder, subpath, b"x"),
+                Err(Error::InvalidOperation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_write_file_enforces_folder_rules() {
+        let dir = tempdir().unwrap();
+        let chest = HallChest::with_base_path(dir.path().to_path_buf()).unwrap();
+
+        let hall_id = Uuid::new_v4();
+        let path = chest
+            .init_hall_chest(hall_id, "Test Hall", HallRole::HallAgent)
+            .unwrap();
+
+        let actor = Uuid::new_v4();
+        let mine = format!("personal/{}/notes.txt", actor);
+        let theirs = format!("personal/{}/notes.txt", Uuid::new_v4());
+
+        let result = chest.write_file(hall_id, actor, HallRole::HallBuilder, &theirs, b"x");
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+        assert!(!path.join(&theirs).exists());
+
+        chest
+            .write_file(hall_id, actor, HallRole::HallAgent, &mine, b"x")
+            .unwrap();
+        chest
+            .write_file(
+                hall_id,
+                actor,
+                HallRole::HallAgent,
+                "shared/notes.txt",
+                b"x",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_write_file_never_touches_metadata() {
+        let dir = tempdir().unwrap();
+        let chest = HallChest::with_base_path(dir.path().to_path_buf()).unwrap();
+
+        let hall_id = Uuid::new_v4();
+        chest
+            .init_hall_chest(hall_id, "Test Hall", HallRole::HallAgent)
+            .unwrap();
+        chest.set_quota(hall_id, Some(1 << 20)).unwrap();
+
+        let result = chest.write_file(
+            hall_id,
+            Uuid::new_v4(),
+            HallRole::HallBuilder,
+            HALL_META_FILE,
+            b"{}",
+        );
+        assert!(matches!(result, Err(Error::PermissionDenied(_))));
+        assert_eq!(chest.quota(hall_id).unwrap(), Some(1 << 20));
+    }
 }
@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::models::HallRole;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Database error: {0}")]
@@ -10,18 +12,54 @@ pub enum Error {
     #[error("Authentication failed: {0}")]
     Authentication(String),
 
+    #[error("Password hash error: {0}")]
+    PasswordHash(#[from] argon2::password_hash::Error),
+
     #[error("Permission denied: {0}")]
     PermissionDenied(String),
 
+    #[error("You need to be {required} or higher to do this")]
+    RoleTooLow {
+        required: HallRole,
+        actual: HallRole,
+    },
+
+    #[error("Only the current host can do this")]
+    NotHost,
+
     #[error("Not found: {0}")]
     NotFound(String),
 
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
 
+    #[error("Username {0} is already taken")]
+    UsernameTaken(String),
+
     #[error("Invitation error: {0}")]
     Invitation(String),
 
+    #[error("You are banned from this Hall")]
+    Banned,
+
+    #[error("Invite has expired")]
+    InviteExpired,
+
+    #[error("Invite has reached its maximum number of uses")]
+    InviteExhausted,
+
+    #[error("Slow mode is on, try again in {retry_after}s")]
+    SlowMode { retry_after: u64 },
+
+    #[error("Hall Chest quota exceeded: {required} bytes needed, {quota} allowed")]
+    QuotaExceeded { quota: u64, required: u64 },
+
+    #[error("Database schema version {found} is newer than this app supports ({supported})")]
+    SchemaTooNew { found: u32, supported: u32 },
+
+    #[error("Applied migration {version} does not match this app's copy")]
+    MigrationChecksumMismatch { version: u32 },
+
     #[error("Hosting error: {0}")]
     Hosting(String),
 
@@ -33,3 +71,36 @@ pub enum Error {
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+
+    #[test]
+    fn test_wrapped_errors_keep_their_source() {
+        let err = Error::from(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "chest is read-only",
+        ));
+        let source = err.source().expect("io error should be the source");
+        let io = source.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(io.kind(), std::io::ErrorKind::PermissionDenied);
+
+        let err = Error::from(rusqlite::Error::QueryReturnedNoRows);
+        assert!(err
+            .source()
+            .and_then(|s| s.downcast_ref::<rusqlite::Error>())
+            .is_some());
+
+        let err = Error::from(serde_json::from_str::<u32>("nope").unwrap_err());
+        assert!(err
+            .source()
+            .and_then(|s| s.downcast_ref::<serde_json::Error>())
+            .is_some());
+
+        // Leaf variants have no underlying cause
+        assert!(Error::NotHost.source().is_none());
+    }
+}
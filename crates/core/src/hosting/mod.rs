@@ -53,6 +53,15 @@ impl HostingState {
         self.host_id == Some(user_id)
     }
 
+    /// Require that a user is currently the host
+    pub fn require_host(&self, user_id: Uuid) -> Result<()> {
+        if self.is_host(user_id) {
+            Ok(())
+        } else {
+            Err(Error::NotHost)
+        }
+    }
+
     /// Attempt to become host when entering an empty Hall
     #[instrument(skip(self))]
     pub fn try_become_initial_host(&mut self, user_id: Uuid, role: HallRole) -> Result<bool> {
@@ -107,7 +116,7 @@ impl HostingState {
             .collect();
 
         // Sort by role priority (highest first)
-        candidates.sort_by(|a, b| b.role.hosting_priority().cmp(&a.role.hosting_priority()));
+        candidates.sort_by_key(|m| std::cmp::Reverse(m.role.hosting_priority()));
 
         if let Some(candidate) = candidates.first() {
             HostElectionResult::PromptTakeover(candidate.user_id)
@@ -156,6 +165,11 @@ mod tests {
             .try_become_initial_host(user_id, HallRole::HallAgent)
             .unwrap());
         assert!(state.is_host(user_id));
+        assert!(state.require_host(user_id).is_ok());
+        assert!(matches!(
+            state.require_host(Uuid::new_v4()),
+            Err(Error::NotHost)
+        ));
     }
 
     #[test]
@@ -0,0 +1,183 @@
+//! Consistency checks for stored Hall state
+//!
+//! These are the rules the rest of the crate assumes hold for every Hall.
+//! They are exposed as checks rather than assertions so they can back a
+//! debug command or run after migrations in tests.
+
+use std::fmt;
+
+use rusqlite::params;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::HallRole;
+use crate::storage::parse::parse_uuid;
+use crate::storage::{Database, HallStore};
+
+/// A broken invariant found in a Hall
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The Hall's owner has no Builder membership
+    OwnerNotBuilder { owner_id: Uuid },
+    /// The Hall does not have exactly one Builder
+    BuilderCount { count: u32 },
+    /// `current_host_id` points at someone who is not a member
+    HostNotMember { host_id: Uuid },
+    /// A membership stores a role value outside the known range
+    InvalidRole { user_id: Uuid, role: u8 },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::OwnerNotBuilder { owner_id } => {
+                write!(f, "Owner {} has no Builder membership", owner_id)
+            }
+            InvariantViolation::BuilderCount { count } => {
+                write!(f, "Expected exactly one Builder, found {}", count)
+            }
+            InvariantViolation::HostNotMember { host_id } => {
+                write!(f, "Host {} is not a member", host_id)
+            }
+            InvariantViolation::InvalidRole { user_id, role } => {
+                write!(f, "Member {} has invalid role value {}", user_id, role)
+            }
+        }
+    }
+}
+
+/// Check a Hall's stored state, returning every violation found
+///
+/// Runs in a single transaction so all checks see the same snapshot.
+/// An empty list means the Hall is consistent.
+#[instrument(skip(db))]
+pub fn check_hall_invariants(db: &Database, hall_id: Uuid) -> Result<Vec<InvariantViolation>> {
+    let builder = HallRole::HallBuilder as u8;
+    let fellow = HallRole::HallFellow as u8;
+
+    db.transaction(|conn| {
+        let halls = HallStore::new(conn);
+        let hall = halls
+            .find_by_id(hall_id)?
+            .ok_or_else(|| Error::NotFound(format!("Hall {}", hall_id)))?;
+        let mut violations = Vec::new();
+
+        let mut stmt = conn.prepare(
+            "SELECT user_id, role FROM memberships
+             WHERE hall_id = ?1 AND (role < ?2 OR role > ?3)",
+        )?;
+        let invalid = stmt
+            .query_map(params![hall_id.to_string(), fellow, builder], |row| {
+                Ok(InvariantViolation::InvalidRole {
+                    user_id: parse_uuid(&row.get::<_, String>(0)?)?,
+                    role: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        violations.extend(invalid);
+
+        let owner_is_builder: bool = conn.query_row(
+            "SELECT EXISTS(
+                 SELECT 1 FROM memberships WHERE hall_id = ?1 AND user_id = ?2 AND role = ?3
+             )",
+            params![hall_id.to_string(), hall.owner_id.to_string(), builder],
+            |row| row.get(0),
+        )?;
+        if !owner_is_builder {
+            violations.push(InvariantViolation::OwnerNotBuilder {
+                owner_id: hall.owner_id,
+            });
+        }
+
+        let builders: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM memberships WHERE hall_id = ?1 AND role = ?2",
+            params![hall_id.to_string(), builder],
+            |row| row.get(0),
+        )?;
+        if builders != 1 {
+            violations.push(InvariantViolation::BuilderCount { count: builders });
+        }
+
+        if let Some((host_id, _)) = halls.get_hall_host(hall_id)? {
+            if halls.get_membership(host_id, hall_id)?.is_none() {
+                violations.push(InvariantViolation::HostNotMember { host_id });
+            }
+        }
+
+        Ok(violations)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Hall, Membership, User};
+
+    /// Create a consistent Hall owned by a Builder (test helper)
+    fn setup(db: &Database) -> (Uuid, Uuid) {
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+
+        let hall = Hall::new("Test Hall".into(), user.id);
+        db.halls().create(&hall).unwrap();
+        db.halls()
+            .add_member(&Membership::new(user.id, hall.id, HallRole::HallBuilder))
+            .unwrap();
+        db.halls().set_hall_host(hall.id, user.id, 1).unwrap();
+
+        (user.id, hall.id)
+    }
+
+    #[test]
+    fn test_consistent_hall_has_no_violations() {
+        let db = Database::open_in_memory().unwrap();
+        let (_, hall_id) = setup(&db);
+
+        assert!(check_hall_invariants(&db, hall_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reports_every_violation() {
+        let db = Database::open_in_memory().unwrap();
+        let (owner_id, hall_id) = setup(&db);
+        let stranger = Uuid::new_v4();
+
+        db.transaction(|conn| {
+            conn.execute(
+                "UPDATE memberships SET role = 7 WHERE user_id = ?1",
+                params![owner_id.to_string()],
+            )?;
+            conn.execute(
+                "UPDATE halls SET current_host_id = ?1 WHERE id = ?2",
+                params![stranger.to_string(), hall_id.to_string()],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+
+        let violations = check_hall_invariants(&db, hall_id).unwrap();
+        assert_eq!(
+            violations,
+            vec![
+                InvariantViolation::InvalidRole {
+                    user_id: owner_id,
+                    role: 7
+                },
+                InvariantViolation::OwnerNotBuilder { owner_id },
+                InvariantViolation::BuilderCount { count: 0 },
+                InvariantViolation::HostNotMember { host_id: stranger },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_hall_is_not_found() {
+        let db = Database::open_in_memory().unwrap();
+
+        assert!(matches!(
+            check_hall_invariants(&db, Uuid::new_v4()),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
@@ -5,15 +5,19 @@
 pub mod chest;
 pub mod error;
 pub mod hosting;
+pub mod invariants;
 pub mod models;
+pub mod net;
 pub mod permissions;
 pub mod storage;
 
 pub use chest::HallChest;
 pub use error::{Error, Result};
 pub use hosting::*;
+pub use invariants::{check_hall_invariants, InvariantViolation};
 pub use models::*;
 pub use permissions::*;
 pub use storage::{
-    Database, HallRepository, InviteRepository, MessageRepository, Storage, UserRepository,
+    Database, DbOptions, ExportedUser, HallExport, HallRepository, InviteRepository,
+    MaintenanceReport, MessageRepository, Storage, UserRepository,
 };
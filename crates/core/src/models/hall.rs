@@ -6,6 +6,11 @@ use uuid::Uuid;
 
 use super::ParlorId;
 
+/// Hall setting key: minimum seconds between messages from one member
+pub const SETTING_SLOW_MODE_SECS: &str = "slow_mode_secs";
+/// Hall setting key: message shown to members when they join
+pub const SETTING_WELCOME_MESSAGE: &str = "welcome_message";
+
 /// A Hall is a shared workspace with members, roles, and chat
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hall {
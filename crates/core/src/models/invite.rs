@@ -47,6 +47,11 @@ impl Invite {
         self
     }
 
+    /// Limit the invite to a single redemption
+    pub fn single_use(self) -> Self {
+        self.with_max_uses(1)
+    }
+
     pub fn is_valid(&self) -> bool {
         if self.is_revoked {
             return false;
@@ -4,6 +4,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::error::Error;
+
 /// Hall roles in priority order (highest to lowest)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[repr(u8)]
@@ -21,6 +23,17 @@ pub enum HallRole {
 }
 
 impl HallRole {
+    /// Canonical identifier, e.g. "builder" (stable, for config and logs)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HallRole::HallBuilder => "builder",
+            HallRole::HallPrefect => "prefect",
+            HallRole::HallModerator => "moderator",
+            HallRole::HallAgent => "agent",
+            HallRole::HallFellow => "fellow",
+        }
+    }
+
     pub fn display_name(&self) -> &'static str {
         match self {
             HallRole::HallBuilder => "Hall Builder",
@@ -70,6 +83,29 @@ impl std::fmt::Display for HallRole {
     }
 }
 
+impl std::str::FromStr for HallRole {
+    type Err = Error;
+
+    /// Parse a role from its canonical, short or display name
+    ///
+    /// Case and spacing are ignored, so "prefect", "Prefect",
+    /// "Hall Prefect" and "HallPrefect" all parse.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-')
+            .collect::<String>()
+            .to_lowercase();
+        let name = normalized.strip_prefix("hall").unwrap_or(&normalized);
+
+        HallRole::all_by_priority()
+            .iter()
+            .copied()
+            .find(|role| role.as_str() == name)
+            .ok_or_else(|| Error::InvalidOperation(format!("Unknown role: {}", s)))
+    }
+}
+
 /// A user's membership in a Hall
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Membership {
@@ -102,4 +138,85 @@ pub struct MemberInfo {
     pub role: HallRole,
     pub is_online: bool,
     pub is_host: bool,
+    /// Color to render the member with, as `#rrggbb`
+    #[serde(default)]
+    pub color: String,
+}
+
+/// A moderation action recorded in a Hall's audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModerationAction {
+    Kick,
+    Ban,
+    Promote,
+    Demote,
+}
+
+impl ModerationAction {
+    /// Canonical identifier, e.g. "kick" (stable, stored in the log)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationAction::Kick => "kick",
+            ModerationAction::Ban => "ban",
+            ModerationAction::Promote => "promote",
+            ModerationAction::Demote => "demote",
+        }
+    }
+}
+
+impl std::fmt::Display for ModerationAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for ModerationAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kick" => Ok(ModerationAction::Kick),
+            "ban" => Ok(ModerationAction::Ban),
+            "promote" => Ok(ModerationAction::Promote),
+            "demote" => Ok(ModerationAction::Demote),
+            _ => Err(Error::InvalidOperation(format!(
+                "Unknown moderation action: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// One row of a Hall's moderation log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationLogEntry {
+    pub hall_id: Uuid,
+    pub actor_id: Uuid,
+    pub target_id: Uuid,
+    pub action: ModerationAction,
+    pub reason: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_string_round_trip() {
+        for role in HallRole::all_by_priority() {
+            assert_eq!(role.as_str().parse::<HallRole>().unwrap(), *role);
+            assert_eq!(role.short_name().parse::<HallRole>().unwrap(), *role);
+            assert_eq!(role.display_name().parse::<HallRole>().unwrap(), *role);
+            assert_eq!(role.to_string().parse::<HallRole>().unwrap(), *role);
+            assert_eq!(format!("{:?}", role).parse::<HallRole>().unwrap(), *role);
+        }
+    }
+
+    #[test]
+    fn test_unknown_role_rejected() {
+        assert!("".parse::<HallRole>().is_err());
+        assert!("hall".parse::<HallRole>().is_err());
+        assert!("owner".parse::<HallRole>().is_err());
+    }
 }
@@ -16,6 +16,11 @@ pub struct Message {
     pub created_at: DateTime<Utc>,
     pub edited_at: Option<DateTime<Utc>>,
     pub is_deleted: bool,
+    /// When the message was soft-deleted (purged after the retention window)
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The message this one replies to, in the same Hall
+    #[serde(default)]
+    pub reply_to: Option<Uuid>,
 }
 
 impl Message {
@@ -28,8 +33,56 @@ impl Message {
             created_at: Utc::now(),
             edited_at: None,
             is_deleted: false,
+            deleted_at: None,
+            reply_to: None,
         }
     }
+
+    pub fn with_reply_to(mut self, message_id: Uuid) -> Self {
+        self.reply_to = Some(message_id);
+        self
+    }
+
+    /// Extract `@username` mentions from the content
+    ///
+    /// A mention is an `@` that does not follow a word character (so
+    /// `email@domain` is ignored), followed by letters, digits or
+    /// underscores. Names are returned without the `@`, deduplicated, in
+    /// order of first appearance.
+    pub fn extract_mentions(&self) -> Vec<String> {
+        let mut mentions: Vec<String> = Vec::new();
+        let mut prev: Option<char> = None;
+        let mut chars = self.content.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let at_word_boundary = !prev.is_some_and(is_mention_char);
+            prev = Some(c);
+
+            if c != '@' || !at_word_boundary {
+                continue;
+            }
+
+            let mut name = String::new();
+            while let Some(&next) = chars.peek() {
+                if !is_mention_char(next) {
+                    break;
+                }
+                name.push(next);
+                prev = Some(next);
+                chars.next();
+            }
+
+            if !name.is_empty() && !mentions.contains(&name) {
+                mentions.push(name);
+            }
+        }
+
+        mentions
+    }
+}
+
+fn is_mention_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
 }
 
 /// Message with sender information for display
@@ -41,6 +94,19 @@ pub struct MessageDisplay {
     pub content: String,
     pub timestamp: DateTime<Utc>,
     pub is_edited: bool,
+    pub is_pinned: bool,
+    /// Sent by a bot account rather than a person
+    #[serde(default)]
+    pub sender_is_bot: bool,
+    /// The message this one replies to, if any
+    #[serde(default)]
+    pub reply_to: Option<Uuid>,
+    /// Color to render the sender with, as `#rrggbb`
+    #[serde(default)]
+    pub sender_color: String,
+    /// Aggregated reactions as (emoji, count), in first-reacted order
+    #[serde(default)]
+    pub reactions: Vec<(String, u32)>,
 }
 
 impl MessageDisplay {
@@ -52,3 +118,27 @@ impl MessageDisplay {
         self.timestamp.format("%Y-%m-%d").to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mentions(content: &str) -> Vec<String> {
+        Message::new(Uuid::new_v4(), Uuid::new_v4(), content.into()).extract_mentions()
+    }
+
+    #[test]
+    fn test_extract_mentions() {
+        assert_eq!(mentions("hey @alice and @bob_2!"), vec!["alice", "bob_2"]);
+        assert_eq!(mentions("@alice, @alice: ping"), vec!["alice"]);
+        assert!(mentions("no mentions here").is_empty());
+    }
+
+    #[test]
+    fn test_extract_mentions_edge_cases() {
+        assert!(mentions("mail me at email@domain.com").is_empty());
+        assert_eq!(mentions("@@double"), vec!["double"]);
+        assert!(mentions("trailing @").is_empty());
+        assert_eq!(mentions("(@carol)"), vec!["carol"]);
+    }
+}
@@ -5,9 +5,12 @@
 //!
 //! This module defines the interface but does NOT implement any parlors yet.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::error::{Error, Result};
+
 /// Identifier for a parlor module type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ParlorId(pub Uuid);
@@ -24,6 +27,87 @@ impl Default for ParlorId {
     }
 }
 
+/// Activation lifecycle of a Hall's parlor
+///
+/// Moves Inactive -> Activating -> Active -> Deactivating -> Inactive.
+/// Any other move is rejected with [`Error::InvalidOperation`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParlorState {
+    #[default]
+    Inactive,
+    Activating {
+        parlor: ParlorId,
+    },
+    Active {
+        parlor: ParlorId,
+        since: DateTime<Utc>,
+    },
+    Deactivating {
+        parlor: ParlorId,
+    },
+}
+
+impl ParlorState {
+    /// The parlor that is fully active, if any (what `Hall::active_parlor` stores)
+    pub fn active_parlor(&self) -> Option<ParlorId> {
+        match self {
+            ParlorState::Active { parlor, .. } => Some(*parlor),
+            _ => None,
+        }
+    }
+
+    /// Start activating a parlor in an idle Hall
+    pub fn begin_activation(&mut self, parlor: ParlorId) -> Result<()> {
+        match self {
+            ParlorState::Inactive => {
+                *self = ParlorState::Activating { parlor };
+                Ok(())
+            }
+            _ => Err(self.illegal("activate")),
+        }
+    }
+
+    /// Finish activation, marking the parlor active from now
+    pub fn complete_activation(&mut self) -> Result<()> {
+        match *self {
+            ParlorState::Activating { parlor } => {
+                *self = ParlorState::Active {
+                    parlor,
+                    since: Utc::now(),
+                };
+                Ok(())
+            }
+            _ => Err(self.illegal("complete activation")),
+        }
+    }
+
+    /// Start shutting down the active parlor
+    pub fn begin_deactivation(&mut self) -> Result<()> {
+        match *self {
+            ParlorState::Active { parlor, .. } => {
+                *self = ParlorState::Deactivating { parlor };
+                Ok(())
+            }
+            _ => Err(self.illegal("deactivate")),
+        }
+    }
+
+    /// Finish deactivation, leaving the Hall without a parlor
+    pub fn complete_deactivation(&mut self) -> Result<()> {
+        match self {
+            ParlorState::Deactivating { .. } => {
+                *self = ParlorState::Inactive;
+                Ok(())
+            }
+            _ => Err(self.illegal("complete deactivation")),
+        }
+    }
+
+    fn illegal(&self, action: &str) -> Error {
+        Error::InvalidOperation(format!("Cannot {} parlor while {:?}", action, self))
+    }
+}
+
 /// Parlor module trait - placeholder for future implementation
 ///
 /// Parlors will be able to:
@@ -62,3 +146,46 @@ impl Default for ParlorRegistry {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parlor_state_full_cycle() {
+        let parlor = ParlorId::new();
+        let mut state = ParlorState::default();
+
+        state.begin_activation(parlor).unwrap();
+        assert_eq!(state.active_parlor(), None);
+
+        state.complete_activation().unwrap();
+        assert_eq!(state.active_parlor(), Some(parlor));
+
+        state.begin_deactivation().unwrap();
+        assert_eq!(state.active_parlor(), None);
+
+        state.complete_deactivation().unwrap();
+        assert_eq!(state, ParlorState::Inactive);
+    }
+
+    #[test]
+    fn test_parlor_state_rejects_illegal_moves() {
+        let mut state = ParlorState::Inactive;
+        assert!(matches!(
+            state.begin_deactivation(),
+            Err(Error::InvalidOperation(_))
+        ));
+
+        state.begin_activation(ParlorId::new()).unwrap();
+        state.complete_activation().unwrap();
+        let active = state;
+
+        // Active cannot go back to Activating
+        assert!(matches!(
+            state.begin_activation(ParlorId::new()),
+            Err(Error::InvalidOperation(_))
+        ));
+        assert_eq!(state, active);
+    }
+}
@@ -12,6 +12,12 @@ pub struct User {
     pub password_hash: String,
     pub created_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
+    /// Reserved account used as the sender identity for a bot
+    #[serde(default)]
+    pub is_bot: bool,
+    /// Chosen accent color as `#rrggbb`; see [`User::display_color`]
+    #[serde(default)]
+    pub accent_color: Option<String>,
 }
 
 impl User {
@@ -22,8 +28,56 @@ impl User {
             password_hash,
             created_at: Utc::now(),
             last_login: None,
+            is_bot: false,
+            accent_color: None,
         }
     }
+
+    /// Color to render this user with, as `#rrggbb`
+    ///
+    /// Users who haven't chosen an accent color get one derived from their
+    /// id, so it is the same everywhere without being stored.
+    pub fn display_color(&self) -> String {
+        display_color_for(self.id, self.accent_color.as_deref())
+    }
+}
+
+/// Username prefix reserved for bot accounts
+///
+/// People can't register names starting with it, so a bot's account can't be
+/// claimed before the bot first posts.
+pub const BOT_USERNAME_PREFIX: &str = "bot:";
+
+/// Username of the account a bot with this id sends messages as
+pub fn bot_username(bot_id: &str) -> String {
+    format!("{}{}", BOT_USERNAME_PREFIX, bot_id)
+}
+
+/// Whether a username falls in the namespace reserved for bots
+pub fn is_reserved_username(username: &str) -> bool {
+    username.starts_with(BOT_USERNAME_PREFIX)
+}
+
+/// Accent colors handed out to users who haven't chosen one
+const ACCENT_PALETTE: &[&str] = &[
+    "#e06c75", "#d19a66", "#e5c07b", "#98c379", "#56b6c2", "#61afef", "#c678dd", "#be5046",
+    "#7ec699", "#f08d49", "#6c8ef5", "#cc99cd",
+];
+
+/// Color to render a user with, preferring their chosen accent color
+pub fn display_color_for(user_id: Uuid, accent_color: Option<&str>) -> String {
+    match accent_color {
+        Some(color) => color.to_string(),
+        None => {
+            let index = (user_id.as_u128() % ACCENT_PALETTE.len() as u128) as usize;
+            ACCENT_PALETTE[index].to_string()
+        }
+    }
+}
+
+/// Check that a color is written as `#rrggbb`
+pub(crate) fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
 }
 
 /// Active session for a logged-in user
@@ -50,3 +104,32 @@ impl Session {
         Utc::now() < self.expires_at
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derived_color_is_stable() {
+        let user = User::new("alice".into(), "hash".into());
+        let color = user.display_color();
+
+        assert_eq!(user.display_color(), color);
+        assert_eq!(display_color_for(user.id, None), color);
+        assert!(is_hex_color(&color));
+
+        let chosen = User {
+            accent_color: Some("#112233".into()),
+            ..user
+        };
+        assert_eq!(chosen.display_color(), "#112233");
+    }
+
+    #[test]
+    fn test_hex_color_validation() {
+        assert!(is_hex_color("#A1b2C3"));
+        assert!(!is_hex_color("A1B2C3"));
+        assert!(!is_hex_color("#12345"));
+        assert!(!is_hex_color("#12345g"));
+    }
+}
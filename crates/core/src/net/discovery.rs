@@ -0,0 +1,182 @@
+//! LAN discovery of hosted Halls over UDP broadcast
+//!
+//! A host periodically announces its Hall to the local network and peers
+//! listen for announcements for a short while. This complements invite
+//! URLs for people on the same network; it is not authenticated, so a
+//! discovered Hall still has to be joined with an invite.
+
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::error::Result;
+
+/// UDP port announcements are broadcast to
+pub const DISCOVERY_PORT: u16 = 47474;
+
+/// Tag identifying Exom announcements among other broadcast traffic
+const ANNOUNCEMENT_TAG: &str = "exom-hall";
+
+/// Largest announcement accepted (a Hall name is far shorter than this)
+const MAX_ANNOUNCEMENT_BYTES: usize = 1024;
+
+/// A Hall announced on the local network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredHall {
+    pub hall_id: Uuid,
+    pub hall_name: String,
+    /// Where the host accepts connections
+    pub addr: SocketAddr,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Announcement {
+    tag: String,
+    hall_id: Uuid,
+    hall_name: String,
+    port: u16,
+}
+
+/// Broadcast a Hall on the local network
+///
+/// Networks that block broadcast are not an error: the failure is logged
+/// and peers simply won't see the Hall.
+pub fn announce(hall_id: Uuid, hall_name: &str, port: u16) -> Result<()> {
+    announce_to(
+        SocketAddr::from((Ipv4Addr::BROADCAST, DISCOVERY_PORT)),
+        hall_id,
+        hall_name,
+        port,
+    )
+}
+
+/// Send a Hall announcement to a specific address
+#[instrument(skip(hall_name))]
+pub fn announce_to(target: SocketAddr, hall_id: Uuid, hall_name: &str, port: u16) -> Result<()> {
+    let payload = serde_json::to_vec(&Announcement {
+        tag: ANNOUNCEMENT_TAG.into(),
+        hall_id,
+        hall_name: hall_name.into(),
+        port,
+    })?;
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    let sent = socket
+        .set_broadcast(true)
+        .and_then(|_| socket.send_to(&payload, target));
+    match sent {
+        Ok(_) => Ok(()),
+        Err(e) if is_blocked(&e) => {
+            warn!("LAN announcement blocked: {}", e);
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Listen on [`DISCOVERY_PORT`] for `timeout` and list the Halls heard
+///
+/// The port is bound with address (and, on Unix, port) reuse, so several
+/// Exom instances on one machine can listen at the same time.
+pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredHall>> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT)).into())?;
+    discover_on(&socket.into(), timeout)
+}
+
+/// Collect announcements arriving on `socket` until `timeout` elapses
+///
+/// Each Hall is listed once, at the address it was last announced from.
+/// Packets that are not Exom announcements are ignored.
+#[instrument(skip(socket))]
+pub fn discover_on(socket: &UdpSocket, timeout: Duration) -> Result<Vec<DiscoveredHall>> {
+    let deadline = Instant::now() + timeout;
+    let mut halls: Vec<DiscoveredHall> = Vec::new();
+    let mut buf = [0u8; MAX_ANNOUNCEMENT_BYTES];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let Ok(announcement) = serde_json::from_slice::<Announcement>(&buf[..len]) else {
+            continue;
+        };
+        if announcement.tag != ANNOUNCEMENT_TAG {
+            continue;
+        }
+
+        let hall = DiscoveredHall {
+            hall_id: announcement.hall_id,
+            hall_name: announcement.hall_name,
+            addr: SocketAddr::new(from.ip(), announcement.port),
+        };
+        match halls.iter_mut().find(|h| h.hall_id == hall.hall_id) {
+            Some(existing) => *existing = hall,
+            None => halls.push(hall),
+        }
+    }
+
+    Ok(halls)
+}
+
+/// Errors meaning the network does not allow broadcast
+fn is_blocked(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::PermissionDenied
+            | ErrorKind::AddrNotAvailable
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::HostUnreachable
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_announcement_is_discovered() {
+        let listener = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let target = listener.local_addr().unwrap();
+        let hall_id = Uuid::new_v4();
+
+        announce_to(target, hall_id, "Study Hall", 7000).unwrap();
+        announce_to(target, hall_id, "Study Hall", 7000).unwrap();
+        listener.send_to(b"not json", target).unwrap();
+
+        let halls = discover_on(&listener, Duration::from_millis(200)).unwrap();
+        assert_eq!(
+            halls,
+            vec![DiscoveredHall {
+                hall_id,
+                hall_name: "Study Hall".into(),
+                addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 7000)),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_discover_shares_the_port() {
+        // Two instances on one machine listen at the same time
+        let other = std::thread::spawn(|| discover(Duration::from_millis(300)));
+        std::thread::sleep(Duration::from_millis(50));
+        discover(Duration::from_millis(100)).unwrap();
+        other.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,7 @@
+//! Networking helpers
+//!
+//! Hall traffic itself is not networked yet. What lives here is opt-in
+//! and behind feature flags.
+
+#[cfg(feature = "discovery")]
+pub mod discovery;
@@ -3,6 +3,11 @@
 //! Provides a comprehensive permission matrix and enforcement utilities
 //! for role-based access control in Halls.
 
+use std::path::{Component, Path};
+
+use uuid::Uuid;
+
+use crate::chest::HALL_META_FILE;
 use crate::error::{Error, Result};
 use crate::models::HallRole;
 
@@ -102,6 +107,15 @@ impl PermissionMatrix {
         }
     }
 
+    /// Lowest role allowed to perform an action, if any role is
+    pub fn minimum_role(action: HallAction) -> Option<HallRole> {
+        HallRole::all_by_priority()
+            .iter()
+            .rev()
+            .copied()
+            .find(|role| Self::can_perform(*role, action))
+    }
+
     /// Check if a role can promote/demote to a target role
     pub fn can_change_role(
         actor_role: HallRole,
@@ -123,6 +137,48 @@ impl PermissionMatrix {
         actor_role >= HallRole::HallPrefect
     }
 
+    /// Check if a member can lower their own role to `target_new`
+    ///
+    /// Stepping down is always allowed, whatever the role, but a member can
+    /// never raise their own role. The Builder cannot step down, since a
+    /// Hall must keep exactly one; ownership has to be transferred instead.
+    pub fn can_self_demote(actor_role: HallRole, target_new: HallRole) -> bool {
+        actor_role != HallRole::HallBuilder && target_new < actor_role
+    }
+
+    /// Check if a member can write to a path inside the Hall chest
+    ///
+    /// Rules follow the top-level folder: `shared` is open to every role
+    /// with chest write access, `personal/<user_id>/` belongs to that member
+    /// alone, and `downloads` (where synced files land) along with anything
+    /// outside the standard folders is limited to chest managers. Paths
+    /// that aren't plain relative ones, and the chest's own metadata file,
+    /// are never writable.
+    pub fn can_write_chest_path(role: HallRole, actor_id: Uuid, subpath: &str) -> bool {
+        if !Self::can_perform(role, HallAction::WriteChest) {
+            return false;
+        }
+
+        let mut names = Vec::new();
+        for component in Path::new(subpath).components() {
+            match component {
+                Component::Normal(name) => match name.to_str() {
+                    Some(name) => names.push(name),
+                    None => return false,
+                },
+                _ => return false,
+            }
+        }
+
+        match names.as_slice() {
+            [] | [HALL_META_FILE, ..] => false,
+            ["shared", ..] => true,
+            ["personal", owner, _, ..] => *owner == actor_id.to_string(),
+            ["personal", ..] => false,
+            _ => Self::can_perform(role, HallAction::ManageChest),
+        }
+    }
+
     /// Check if a role can kick another role
     pub fn can_kick(actor_role: HallRole, target_role: HallRole) -> bool {
         // Can only kick roles lower than your own
@@ -156,14 +212,23 @@ impl PermissionMatrix {
 }
 
 /// Require a permission, returning an error if not allowed
+///
+/// Fails with [`Error::RoleTooLow`] naming the lowest role that could
+/// perform the action, or [`Error::PermissionDenied`] if no role can.
 pub fn require_permission(role: HallRole, action: HallAction) -> Result<()> {
     if PermissionMatrix::can_perform(role, action) {
-        Ok(())
-    } else {
-        Err(Error::PermissionDenied(format!(
-            "Role {:?} cannot perform {:?}",
+        return Ok(());
+    }
+
+    match PermissionMatrix::minimum_role(action) {
+        Some(required) => Err(Error::RoleTooLow {
+            required,
+            actual: role,
+        }),
+        None => Err(Error::PermissionDenied(format!(
+            "{} cannot perform {:?}",
             role, action
-        )))
+        ))),
     }
 }
 
@@ -175,21 +240,64 @@ pub fn require_can_change_role(
 ) -> Result<()> {
     if PermissionMatrix::can_change_role(actor_role, target_current, target_new) {
         Ok(())
+    } else if actor_role < HallRole::HallPrefect {
+        Err(Error::RoleTooLow {
+            required: HallRole::HallPrefect,
+            actual: actor_role,
+        })
     } else {
         Err(Error::PermissionDenied(format!(
-            "Cannot change role from {:?} to {:?}",
+            "Cannot change role from {} to {}",
             target_current, target_new
         )))
     }
 }
 
+/// Require ability to lower your own role, returning an error if not allowed
+pub fn require_self_demote(actor_role: HallRole, target_new: HallRole) -> Result<()> {
+    if PermissionMatrix::can_self_demote(actor_role, target_new) {
+        Ok(())
+    } else if actor_role == HallRole::HallBuilder {
+        Err(Error::PermissionDenied(
+            "The Hall Builder must transfer ownership before stepping down".into(),
+        ))
+    } else {
+        Err(Error::PermissionDenied(format!(
+            "Cannot change your own role from {} to {}",
+            actor_role, target_new
+        )))
+    }
+}
+
+/// Require ability to write a chest path, returning an error if not allowed
+pub fn require_can_write_chest_path(role: HallRole, actor_id: Uuid, subpath: &str) -> Result<()> {
+    if PermissionMatrix::can_write_chest_path(role, actor_id, subpath) {
+        Ok(())
+    } else if role < HallRole::HallAgent {
+        Err(Error::RoleTooLow {
+            required: HallRole::HallAgent,
+            actual: role,
+        })
+    } else {
+        Err(Error::PermissionDenied(format!(
+            "{} cannot write to {}",
+            role, subpath
+        )))
+    }
+}
+
 /// Require ability to kick a member, returning an error if not allowed
 pub fn require_can_kick(actor_role: HallRole, target_role: HallRole) -> Result<()> {
     if PermissionMatrix::can_kick(actor_role, target_role) {
         Ok(())
+    } else if actor_role < HallRole::HallModerator {
+        Err(Error::RoleTooLow {
+            required: HallRole::HallModerator,
+            actual: actor_role,
+        })
     } else {
         Err(Error::PermissionDenied(format!(
-            "Cannot kick member with role {:?}",
+            "Cannot kick member with role {}",
             target_role
         )))
     }
@@ -457,7 +565,196 @@ mod tests {
         // Success case
         assert!(require_permission(HallRole::HallBuilder, HallAction::DeleteHall).is_ok());
 
-        // Failure case
-        assert!(require_permission(HallRole::HallFellow, HallAction::DeleteHall).is_err());
+        // Failure case names the role needed
+        assert!(matches!(
+            require_permission(HallRole::HallFellow, HallAction::DeleteHall),
+            Err(Error::RoleTooLow {
+                required: HallRole::HallBuilder,
+                actual: HallRole::HallFellow,
+            })
+        ));
+        assert!(matches!(
+            require_permission(HallRole::HallAgent, HallAction::KickMembers),
+            Err(Error::RoleTooLow {
+                required: HallRole::HallModerator,
+                ..
+            })
+        ));
+
+        // No role can invite as Builder
+        assert!(matches!(
+            require_permission(
+                HallRole::HallBuilder,
+                HallAction::InviteWithRole(HallRole::HallBuilder)
+            ),
+            Err(Error::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_require_kick_and_role_change() {
+        assert!(require_can_kick(HallRole::HallModerator, HallRole::HallAgent).is_ok());
+        assert!(matches!(
+            require_can_kick(HallRole::HallAgent, HallRole::HallFellow),
+            Err(Error::RoleTooLow {
+                required: HallRole::HallModerator,
+                ..
+            })
+        ));
+        // Outranked target is a plain denial, not a role shortfall
+        assert!(matches!(
+            require_can_kick(HallRole::HallModerator, HallRole::HallPrefect),
+            Err(Error::PermissionDenied(_))
+        ));
+
+        assert!(matches!(
+            require_can_change_role(
+                HallRole::HallModerator,
+                HallRole::HallFellow,
+                HallRole::HallAgent
+            ),
+            Err(Error::RoleTooLow {
+                required: HallRole::HallPrefect,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_role_too_low_display() {
+        let err = Error::RoleTooLow {
+            required: HallRole::HallPrefect,
+            actual: HallRole::HallAgent,
+        };
+        assert_eq!(
+            err.to_string(),
+            "You need to be Hall Prefect or higher to do this"
+        );
+    }
+
+    #[test]
+    fn test_self_demote() {
+        // A Prefect can step down but not promote themselves
+        assert!(PermissionMatrix::can_self_demote(
+            HallRole::HallPrefect,
+            HallRole::HallFellow
+        ));
+        assert!(!PermissionMatrix::can_self_demote(
+            HallRole::HallPrefect,
+            HallRole::HallPrefect
+        ));
+        assert!(!PermissionMatrix::can_self_demote(
+            HallRole::HallPrefect,
+            HallRole::HallBuilder
+        ));
+        assert!(require_self_demote(HallRole::HallPrefect, HallRole::HallFellow).is_ok());
+        assert!(matches!(
+            require_self_demote(HallRole::HallFellow, HallRole::HallAgent),
+            Err(Error::PermissionDenied(_))
+        ));
+
+        // The Builder cannot leave the Hall ownerless
+        assert!(!PermissionMatrix::can_self_demote(
+            HallRole::HallBuilder,
+            HallRole::HallPrefect
+        ));
+
+        // Changing someone else's role is unaffected
+        assert!(!PermissionMatrix::can_change_role(
+            HallRole::HallPrefect,
+            HallRole::HallPrefect,
+            HallRole::HallFellow
+        ));
+    }
+
+    #[test]
+    fn test_chest_path_permissions() {
+        let me = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let mine = format!("personal/{}/notes.txt", me);
+        let theirs = format!("personal/{}/notes.txt", other);
+
+        let agent = HallRole::HallAgent;
+        assert!(PermissionMatrix::can_write_chest_path(
+            agent,
+            me,
+            "shared/notes.txt"
+        ));
+        assert!(PermissionMatrix::can_write_chest_path(agent, me, &mine));
+        assert!(!PermissionMatrix::can_write_chest_path(agent, me, &theirs));
+        assert!(!PermissionMatrix::can_write_chest_path(
+            agent,
+            me,
+            "personal/notes.txt"
+        ));
+        assert!(!PermissionMatrix::can_write_chest_path(
+            agent,
+            me,
+            "downloads/file.bin"
+        ));
+        assert!(!PermissionMatrix::can_write_chest_path(
+            agent,
+            me,
+            "stray.txt"
+        ));
+
+        let prefect = HallRole::HallPrefect;
+        assert!(PermissionMatrix::can_write_chest_path(
+            prefect,
+            me,
+            "downloads/file.bin"
+        ));
+        assert!(!PermissionMatrix::can_write_chest_path(
+            prefect, me, &theirs
+        ));
+
+        // Personal folders stay per member even for the Builder
+        let builder = HallRole::HallBuilder;
+        assert!(PermissionMatrix::can_write_chest_path(builder, me, &mine));
+        assert!(!PermissionMatrix::can_write_chest_path(
+            builder, me, &theirs
+        ));
+
+        // Fellows have no chest write access anywhere
+        assert!(matches!(
+            require_can_write_chest_path(HallRole::HallFellow, me, "shared/notes.txt"),
+            Err(Error::RoleTooLow {
+                required: HallRole::HallAgent,
+                ..
+            })
+        ));
+        assert!(matches!(
+            require_can_write_chest_path(agent, me, &theirs),
+            Err(Error::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_chest_path_rejects_traversal() {
+        let me = Uuid::new_v4();
+        let sneaky = format!("shared/../personal/{}/x", Uuid::new_v4());
+        for subpath in [
+            sneaky.as_str(),
+            "shared/../downloads/x",
+            "/shared/notes.txt",
+        ] {
+            assert!(
+                !PermissionMatrix::can_write_chest_path(HallRole::HallBuilder, me, subpath),
+                "{} should be rejected",
+                subpath
+            );
+        }
+    }
+
+    #[test]
+    fn test_chest_metadata_is_reserved() {
+        let me = Uuid::new_v4();
+        for role in [HallRole::HallPrefect, HallRole::HallBuilder] {
+            assert!(!PermissionMatrix::can_write_chest_path(
+                role,
+                me,
+                HALL_META_FILE
+            ));
+        }
     }
 }
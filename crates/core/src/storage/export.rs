@@ -0,0 +1,373 @@
+//! Hall export and import
+//!
+//! A Hall is exported as newline-delimited JSON, one tagged record per
+//! line, so backups can be streamed and inspected with ordinary tools.
+
+use std::io::{BufRead, Write};
+
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::parse::{parse_datetime, parse_datetime_opt, parse_uuid, role_from_u8, OptionalExt};
+use super::Database;
+use crate::error::{Error, Result};
+use crate::models::{Hall, Membership, Message};
+
+/// A user referenced by an exported Hall
+///
+/// Password hashes are never exported. Imported users get an empty hash,
+/// which login always refuses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedUser {
+    pub id: Uuid,
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub is_bot: bool,
+}
+
+/// Everything needed to recreate a Hall in another database
+///
+/// Covers the Hall row, its members and every message (including
+/// soft-deleted ones). Reactions and pins are not exported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HallExport {
+    pub hall: Hall,
+    pub users: Vec<ExportedUser>,
+    pub memberships: Vec<Membership>,
+    pub messages: Vec<Message>,
+}
+
+/// One line of an NDJSON export
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ExportRecord {
+    Hall(Hall),
+    User(ExportedUser),
+    Membership(Membership),
+    Message(Message),
+}
+
+impl Database {
+    /// Collect a Hall, its members and its messages
+    #[instrument(skip(self))]
+    pub fn export_hall(&self, hall_id: Uuid) -> Result<HallExport> {
+        let hall = self
+            .halls()
+            .find_by_id(hall_id)?
+            .ok_or_else(|| Error::NotFound(format!("Hall {}", hall_id)))?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT id, username, created_at, is_bot FROM users
+             WHERE id = ?2
+                OR id IN (SELECT user_id FROM memberships WHERE hall_id = ?1)
+                OR id IN (SELECT sender_id FROM messages WHERE hall_id = ?1)
+             ORDER BY created_at",
+        )?;
+        let users = stmt
+            .query_map(
+                params![hall_id.to_string(), hall.owner_id.to_string()],
+                |row| {
+                    Ok(ExportedUser {
+                        id: parse_uuid(&row.get::<_, String>(0)?)?,
+                        username: row.get(1)?,
+                        created_at: parse_datetime(&row.get::<_, String>(2)?)?,
+                        is_bot: row.get::<_, i32>(3)? != 0,
+                    })
+                },
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT id, user_id, hall_id, role, joined_at, is_online FROM memberships
+             WHERE hall_id = ?1
+             ORDER BY joined_at",
+        )?;
+        let memberships = stmt
+            .query_map(params![hall_id.to_string()], |row| {
+                Ok(Membership {
+                    id: parse_uuid(&row.get::<_, String>(0)?)?,
+                    user_id: parse_uuid(&row.get::<_, String>(1)?)?,
+                    hall_id: parse_uuid(&row.get::<_, String>(2)?)?,
+                    role: role_from_u8(row.get::<_, u8>(3)?),
+                    joined_at: parse_datetime(&row.get::<_, String>(4)?)?,
+                    is_online: row.get::<_, i32>(5)? != 0,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT id, hall_id, sender_id, content, created_at, edited_at, is_deleted, deleted_at, reply_to
+             FROM messages WHERE hall_id = ?1
+             ORDER BY sequence",
+        )?;
+        let messages = stmt
+            .query_map(params![hall_id.to_string()], |row| {
+                Ok(Message {
+                    id: parse_uuid(&row.get::<_, String>(0)?)?,
+                    hall_id: parse_uuid(&row.get::<_, String>(1)?)?,
+                    sender_id: parse_uuid(&row.get::<_, String>(2)?)?,
+                    content: row.get(3)?,
+                    created_at: parse_datetime(&row.get::<_, String>(4)?)?,
+                    edited_at: parse_datetime_opt(row.get::<_, Option<String>>(5)?)?,
+                    is_deleted: row.get::<_, i32>(6)? != 0,
+                    deleted_at: parse_datetime_opt(row.get::<_, Option<String>>(7)?)?,
+                    reply_to: row
+                        .get::<_, Option<String>>(8)?
+                        .map(|id| parse_uuid(&id))
+                        .transpose()?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(HallExport {
+            hall,
+            users,
+            memberships,
+            messages,
+        })
+    }
+
+    /// Write a Hall export as newline-delimited JSON
+    #[instrument(skip(self, writer))]
+    pub fn export_hall_to_writer<W: Write>(&self, hall_id: Uuid, writer: &mut W) -> Result<()> {
+        let export = self.export_hall(hall_id)?;
+
+        let records = std::iter::once(ExportRecord::Hall(export.hall))
+            .chain(export.users.into_iter().map(ExportRecord::User))
+            .chain(export.memberships.into_iter().map(ExportRecord::Membership))
+            .chain(export.messages.into_iter().map(ExportRecord::Message));
+        for record in records {
+            serde_json::to_writer(&mut *writer, &record)?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Recreate a Hall from newline-delimited JSON, returning its id
+    ///
+    /// Rows that already exist are left untouched, so importing the same
+    /// export twice is harmless. Everything is written in one transaction;
+    /// it fails as a whole with [`Error::UsernameTaken`] if an exported
+    /// username already belongs to a different local account.
+    #[instrument(skip(self, reader))]
+    pub fn import_hall<R: BufRead>(&self, reader: R) -> Result<Uuid> {
+        let mut hall = None;
+        let mut users = Vec::new();
+        let mut memberships = Vec::new();
+        let mut messages = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line)? {
+                ExportRecord::Hall(h) => hall = Some(h),
+                ExportRecord::User(u) => users.push(u),
+                ExportRecord::Membership(m) => memberships.push(m),
+                ExportRecord::Message(m) => messages.push(m),
+            }
+        }
+
+        let hall: Hall =
+            hall.ok_or_else(|| Error::InvalidOperation("Export has no hall record".into()))?;
+
+        self.transaction(|tx| {
+            for user in &users {
+                let existing = tx
+                    .query_row(
+                        "SELECT id FROM users WHERE username = ?1",
+                        params![user.username],
+                        |row| parse_uuid(&row.get::<_, String>(0)?),
+                    )
+                    .optional()?;
+                if existing.is_some_and(|id| id != user.id) {
+                    return Err(Error::UsernameTaken(user.username.clone()));
+                }
+
+                tx.execute(
+                    "INSERT OR IGNORE INTO users (id, username, password_hash, created_at, is_bot)
+                     VALUES (?1, ?2, '', ?3, ?4)",
+                    params![
+                        user.id.to_string(),
+                        user.username,
+                        user.created_at.to_rfc3339(),
+                        user.is_bot as i32,
+                    ],
+                )?;
+            }
+
+            tx.execute(
+                "INSERT OR IGNORE INTO halls (id, name, description, owner_id, created_at, active_parlor, current_host_id, election_epoch)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    hall.id.to_string(),
+                    hall.name,
+                    hall.description,
+                    hall.owner_id.to_string(),
+                    hall.created_at.to_rfc3339(),
+                    hall.active_parlor.map(|p| p.0.to_string()),
+                    hall.current_host_id.map(|h| h.to_string()),
+                    hall.election_epoch,
+                ],
+            )?;
+
+            for membership in &memberships {
+                tx.execute(
+                    "INSERT OR IGNORE INTO memberships (id, user_id, hall_id, role, joined_at, is_online)
+                     VALUES (?1, ?2, ?3, ?4, ?5, 0)",
+                    params![
+                        membership.id.to_string(),
+                        membership.user_id.to_string(),
+                        membership.hall_id.to_string(),
+                        membership.role as u8,
+                        membership.joined_at.to_rfc3339(),
+                    ],
+                )?;
+            }
+
+            for message in &messages {
+                tx.execute(
+                    "INSERT OR IGNORE INTO messages (id, hall_id, sender_id, content, created_at, edited_at, is_deleted, deleted_at, reply_to)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        message.id.to_string(),
+                        message.hall_id.to_string(),
+                        message.sender_id.to_string(),
+                        message.content,
+                        message.created_at.to_rfc3339(),
+                        message.edited_at.map(|t| t.to_rfc3339()),
+                        message.is_deleted as i32,
+                        message.deleted_at.map(|t| t.to_rfc3339()),
+                        message.reply_to.map(|id| id.to_string()),
+                    ],
+                )?;
+            }
+
+            Ok(hall.id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{HallRole, User};
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let source = Database::open_in_memory().unwrap();
+        let owner = User::new("alice".into(), "hash".into());
+        let guest = User::new("bob".into(), "hash".into());
+        source.users().create(&owner).unwrap();
+        source.users().create(&guest).unwrap();
+
+        let hall = Hall::new("Test Hall".into(), owner.id);
+        source.halls().create(&hall).unwrap();
+        source
+            .halls()
+            .add_member(&Membership::new(owner.id, hall.id, HallRole::HallBuilder))
+            .unwrap();
+        source
+            .halls()
+            .add_member(&Membership::new(guest.id, hall.id, HallRole::HallFellow))
+            .unwrap();
+        for i in 0..5 {
+            let sender = if i % 2 == 0 { owner.id } else { guest.id };
+            source
+                .messages()
+                .create(&Message::new(hall.id, sender, format!("message {}", i)))
+                .unwrap();
+        }
+
+        let mut buffer = Vec::new();
+        source.export_hall_to_writer(hall.id, &mut buffer).unwrap();
+        assert!(!String::from_utf8_lossy(&buffer).contains("hash"));
+
+        let target = Database::open_in_memory().unwrap();
+        let imported = target.import_hall(buffer.as_slice()).unwrap();
+        assert_eq!(imported, hall.id);
+
+        assert_eq!(target.messages().count_for_hall(hall.id).unwrap(), 5);
+        assert_eq!(target.halls().list_members(hall.id).unwrap().len(), 2);
+        assert_eq!(
+            target.halls().get_user_role(guest.id, hall.id).unwrap(),
+            Some(HallRole::HallFellow)
+        );
+
+        // Importing again changes nothing
+        target.import_hall(buffer.as_slice()).unwrap();
+        assert_eq!(target.messages().count_for_hall(hall.id).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_import_rejects_username_conflicts() {
+        let source = Database::open_in_memory().unwrap();
+        let owner = User::new("alice".into(), "hash".into());
+        source.users().create(&owner).unwrap();
+        let hall = Hall::new("Test Hall".into(), owner.id);
+        source.halls().create(&hall).unwrap();
+        source
+            .halls()
+            .add_member(&Membership::new(owner.id, hall.id, HallRole::HallBuilder))
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        source.export_hall_to_writer(hall.id, &mut buffer).unwrap();
+
+        // A different local account already holds the username
+        let target = Database::open_in_memory().unwrap();
+        target
+            .users()
+            .create(&User::new("alice".into(), "hash".into()))
+            .unwrap();
+
+        assert!(matches!(
+            target.import_hall(buffer.as_slice()),
+            Err(Error::UsernameTaken(name)) if name == "alice"
+        ));
+        assert!(target.halls().find_by_id(hall.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_imported_users_cannot_log_in() {
+        let source = Database::open_in_memory().unwrap();
+        let owner = User::new("alice".into(), "hash".into());
+        source.users().create(&owner).unwrap();
+        let hall = Hall::new("Test Hall".into(), owner.id);
+        source.halls().create(&hall).unwrap();
+        source
+            .halls()
+            .add_member(&Membership::new(owner.id, hall.id, HallRole::HallBuilder))
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        source.export_hall_to_writer(hall.id, &mut buffer).unwrap();
+        let target = Database::open_in_memory().unwrap();
+        target.import_hall(buffer.as_slice()).unwrap();
+
+        for password in ["", "hash"] {
+            assert!(target
+                .users()
+                .verify_and_maybe_rehash("alice", password, &Default::default())
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_import_requires_hall_record() {
+        let db = Database::open_in_memory().unwrap();
+
+        assert!(matches!(
+            db.import_hall("\n".as_bytes()),
+            Err(Error::InvalidOperation(_))
+        ));
+    }
+}
@@ -1,14 +1,21 @@
 //! Hall storage operations
 
+use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use tracing::instrument;
 use uuid::Uuid;
 
 use super::parse::{
     parse_datetime, parse_parlor_id_opt, parse_uuid, parse_uuid_opt, role_from_u8, OptionalExt,
 };
-use crate::error::Result;
-use crate::models::{Hall, HallRole, MemberInfo, Membership};
+use crate::error::{Error, Result};
+use crate::models::{
+    display_color_for, Hall, HallRole, MemberInfo, Membership, ModerationAction,
+    ModerationLogEntry, ParlorId,
+};
+use crate::permissions::require_self_demote;
 
 pub struct HallStore<'a> {
     conn: &'a Connection,
@@ -83,6 +90,16 @@ impl<'a> HallStore<'a> {
         Ok(())
     }
 
+    /// Set or clear the Hall's active parlor
+    #[instrument(skip(self))]
+    pub fn set_active_parlor(&self, hall_id: Uuid, parlor: Option<ParlorId>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE halls SET active_parlor = ?1 WHERE id = ?2",
+            params![parlor.map(|p| p.0.to_string()), hall_id.to_string()],
+        )?;
+        Ok(())
+    }
+
     /// Delete Hall
     #[instrument(skip(self))]
     pub fn delete(&self, hall_id: Uuid) -> Result<()> {
@@ -123,8 +140,14 @@ impl<'a> HallStore<'a> {
     }
 
     /// Add membership
+    ///
+    /// Fails with [`Error::Banned`] if the user is banned from the Hall.
     #[instrument(skip(self, membership), fields(user_id = %membership.user_id, hall_id = %membership.hall_id, role = ?membership.role))]
     pub fn add_member(&self, membership: &Membership) -> Result<()> {
+        if self.is_banned(membership.hall_id, membership.user_id)? {
+            return Err(Error::Banned);
+        }
+
         self.conn.execute(
             "INSERT INTO memberships (id, user_id, hall_id, role, joined_at, is_online)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
@@ -165,13 +188,29 @@ impl<'a> HallStore<'a> {
     }
 
     /// Update membership role
+    ///
+    /// Sets the base role. A deliberate role change supersedes any
+    /// temporary grant, so the member's grant is revoked as well.
     #[instrument(skip(self))]
     pub fn update_role(&self, user_id: Uuid, hall_id: Uuid, new_role: HallRole) -> Result<()> {
         self.conn.execute(
             "UPDATE memberships SET role = ?1 WHERE user_id = ?2 AND hall_id = ?3",
             params![new_role as u8, user_id.to_string(), hall_id.to_string()],
         )?;
-        Ok(())
+        self.revoke_temporary_role(user_id, hall_id)
+    }
+
+    /// Lower a member's own role to `new_role`
+    ///
+    /// Checked against the base role, so stepping down from a temporary
+    /// grant can never raise the stored role. The grant is revoked.
+    #[instrument(skip(self))]
+    pub fn step_down(&self, user_id: Uuid, hall_id: Uuid, new_role: HallRole) -> Result<()> {
+        let base = self
+            .base_role(user_id, hall_id)?
+            .ok_or_else(|| Error::NotFound("Membership not found".into()))?;
+        require_self_demote(base, new_role)?;
+        self.update_role(user_id, hall_id, new_role)
     }
 
     /// Update online status
@@ -200,40 +239,332 @@ impl<'a> HallStore<'a> {
     }
 
     /// List members of a Hall with user info
+    ///
+    /// Roles are effective roles, matching [`get_user_role`](Self::get_user_role).
     #[instrument(skip(self))]
     pub fn list_members(&self, hall_id: Uuid) -> Result<Vec<MemberInfo>> {
         let mut stmt = self.conn.prepare(
-            "SELECT u.id, u.username, m.role, m.is_online, h.current_host_id
+            "SELECT u.id, u.username, MAX(m.role, COALESCE(t.role, 0)) AS effective_role,
+                    m.is_online, h.current_host_id, u.accent_color
              FROM memberships m
              INNER JOIN users u ON u.id = m.user_id
              INNER JOIN halls h ON h.id = m.hall_id
+             LEFT JOIN temporary_roles t
+                 ON t.user_id = m.user_id AND t.hall_id = m.hall_id AND t.expires_at > ?2
              WHERE m.hall_id = ?1
-             ORDER BY m.role DESC, u.username",
+             ORDER BY effective_role DESC, u.username",
         )?;
 
         let members = stmt
+            .query_map(
+                params![hall_id.to_string(), Utc::now().to_rfc3339()],
+                |row| {
+                    let user_id = parse_uuid(&row.get::<_, String>(0)?)?;
+                    let host_id = parse_uuid_opt(row.get::<_, Option<String>>(4)?)?;
+
+                    Ok(MemberInfo {
+                        user_id,
+                        username: row.get(1)?,
+                        role: role_from_u8(row.get::<_, u8>(2)?),
+                        is_online: row.get::<_, i32>(3)? != 0,
+                        is_host: host_id == Some(user_id),
+                        color: display_color_for(
+                            user_id,
+                            row.get::<_, Option<String>>(5)?.as_deref(),
+                        ),
+                    })
+                },
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(members)
+    }
+
+    /// Count members of a Hall without loading them
+    #[instrument(skip(self))]
+    pub fn member_count(&self, hall_id: Uuid) -> Result<u64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM memberships WHERE hall_id = ?1",
+            params![hall_id.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    /// Count members of a Hall currently online
+    #[instrument(skip(self))]
+    pub fn online_count(&self, hall_id: Uuid) -> Result<u64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM memberships WHERE hall_id = ?1 AND is_online = 1",
+            params![hall_id.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    /// Store a Hall setting as JSON, replacing any previous value
+    ///
+    /// Callers should check `HallAction::EditHallSettings` first.
+    #[instrument(skip(self, value))]
+    pub fn set_setting<T: Serialize>(&self, hall_id: Uuid, key: &str, value: &T) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO hall_settings (hall_id, key, value_json) VALUES (?1, ?2, ?3)
+             ON CONFLICT(hall_id, key) DO UPDATE SET value_json = ?3",
+            params![hall_id.to_string(), key, serde_json::to_string(value)?],
+        )?;
+        Ok(())
+    }
+
+    /// Read a Hall setting, or `None` if it was never set
+    #[instrument(skip(self))]
+    pub fn get_setting<T: DeserializeOwned>(&self, hall_id: Uuid, key: &str) -> Result<Option<T>> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value_json FROM hall_settings WHERE hall_id = ?1 AND key = ?2",
+                params![hall_id.to_string(), key],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(json.map(|j| serde_json::from_str(&j)).transpose()?)
+    }
+
+    /// List all settings for a Hall, ordered by key
+    #[instrument(skip(self))]
+    pub fn list_settings(&self, hall_id: Uuid) -> Result<Vec<(String, serde_json::Value)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, value_json FROM hall_settings WHERE hall_id = ?1 ORDER BY key")?;
+
+        let rows = stmt
             .query_map(params![hall_id.to_string()], |row| {
-                let user_id = parse_uuid(&row.get::<_, String>(0)?)?;
-                let host_id = parse_uuid_opt(row.get::<_, Option<String>>(4)?)?;
-
-                Ok(MemberInfo {
-                    user_id,
-                    username: row.get(1)?,
-                    role: role_from_u8(row.get::<_, u8>(2)?),
-                    is_online: row.get::<_, i32>(3)? != 0,
-                    is_host: host_id == Some(user_id),
-                })
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        Ok(members)
+        rows.into_iter()
+            .map(|(key, json)| Ok((key, serde_json::from_str(&json)?)))
+            .collect()
+    }
+
+    /// Ban a user from a Hall, removing their membership
+    ///
+    /// Banning again replaces the reason and time.
+    #[instrument(skip(self, reason))]
+    pub fn ban_user(
+        &self,
+        hall_id: Uuid,
+        user_id: Uuid,
+        banned_by: Uuid,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO hall_bans (hall_id, user_id, banned_by, reason, banned_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(hall_id, user_id) DO UPDATE
+             SET banned_by = ?3, reason = ?4, banned_at = ?5",
+            params![
+                hall_id.to_string(),
+                user_id.to_string(),
+                banned_by.to_string(),
+                reason,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        self.remove_member(user_id, hall_id)?;
+        self.log_moderation(hall_id, banned_by, user_id, ModerationAction::Ban, reason)
+    }
+
+    /// Remove a member on a moderator's behalf and record it
+    ///
+    /// Unlike a ban, the user may rejoin with a new invite.
+    #[instrument(skip(self, reason))]
+    pub fn kick_member(
+        &self,
+        hall_id: Uuid,
+        user_id: Uuid,
+        kicked_by: Uuid,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.remove_member(user_id, hall_id)?;
+        self.log_moderation(hall_id, kicked_by, user_id, ModerationAction::Kick, reason)
+    }
+
+    /// Append an entry to a Hall's moderation log
+    #[instrument(skip(self, reason))]
+    pub fn log_moderation(
+        &self,
+        hall_id: Uuid,
+        actor_id: Uuid,
+        target_id: Uuid,
+        action: ModerationAction,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO moderation_log (hall_id, actor_id, target_id, action, reason, at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                hall_id.to_string(),
+                actor_id.to_string(),
+                target_id.to_string(),
+                action.as_str(),
+                reason,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// List the most recent moderation log entries for a Hall, newest first
+    #[instrument(skip(self))]
+    pub fn list_moderation_log(
+        &self,
+        hall_id: Uuid,
+        limit: u32,
+    ) -> Result<Vec<ModerationLogEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT hall_id, actor_id, target_id, action, reason, at FROM moderation_log
+             WHERE hall_id = ?1
+             ORDER BY id DESC
+             LIMIT ?2",
+        )?;
+
+        let rows = stmt
+            .query_map(params![hall_id.to_string(), limit], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(hall_id, actor_id, target_id, action, reason, at)| {
+                Ok(ModerationLogEntry {
+                    hall_id: parse_uuid(&hall_id)?,
+                    actor_id: parse_uuid(&actor_id)?,
+                    target_id: parse_uuid(&target_id)?,
+                    action: action.parse()?,
+                    reason,
+                    at: parse_datetime(&at)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Lift a ban so the user can rejoin
+    #[instrument(skip(self))]
+    pub fn unban_user(&self, hall_id: Uuid, user_id: Uuid) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM hall_bans WHERE hall_id = ?1 AND user_id = ?2",
+            params![hall_id.to_string(), user_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Check if a user is banned from a Hall
+    #[instrument(skip(self))]
+    pub fn is_banned(&self, hall_id: Uuid, user_id: Uuid) -> Result<bool> {
+        let banned = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM hall_bans WHERE hall_id = ?1 AND user_id = ?2)",
+            params![hall_id.to_string(), user_id.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(banned)
     }
 
     /// Get user's role in a Hall
+    ///
+    /// This is the effective role, including any unexpired temporary grant.
     #[instrument(skip(self))]
     pub fn get_user_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<HallRole>> {
-        let membership = self.get_membership(user_id, hall_id)?;
-        Ok(membership.map(|m| m.role))
+        self.effective_role(user_id, hall_id)
+    }
+
+    /// Get a member's stored role, ignoring temporary grants
+    ///
+    /// Role changes must start from this rather than the effective role,
+    /// or a temporary grant would be written back as permanent.
+    #[instrument(skip(self))]
+    pub fn base_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<HallRole>> {
+        Ok(self.get_membership(user_id, hall_id)?.map(|m| m.role))
+    }
+
+    /// Temporarily raise a member's role until `expires_at`
+    ///
+    /// Replaces any earlier grant for the same member. The grant is removed
+    /// with the membership. Builder cannot be granted temporarily.
+    #[instrument(skip(self))]
+    pub fn grant_temporary_role(
+        &self,
+        user_id: Uuid,
+        hall_id: Uuid,
+        role: HallRole,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        if role == HallRole::HallBuilder {
+            return Err(Error::InvalidOperation(
+                "Builder cannot be granted temporarily".into(),
+            ));
+        }
+        if self.get_membership(user_id, hall_id)?.is_none() {
+            return Err(Error::NotFound("Membership not found".into()));
+        }
+
+        self.conn.execute(
+            "INSERT INTO temporary_roles (user_id, hall_id, role, expires_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(user_id, hall_id) DO UPDATE SET role = ?3, expires_at = ?4",
+            params![
+                user_id.to_string(),
+                hall_id.to_string(),
+                role as u8,
+                expires_at.to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a member's temporary role grant, if any
+    #[instrument(skip(self))]
+    pub fn revoke_temporary_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM temporary_roles WHERE user_id = ?1 AND hall_id = ?2",
+            params![user_id.to_string(), hall_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Higher of a member's base role and any unexpired temporary grant
+    #[instrument(skip(self))]
+    pub fn effective_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<HallRole>> {
+        let Some(membership) = self.get_membership(user_id, hall_id)? else {
+            return Ok(None);
+        };
+
+        let granted = self
+            .conn
+            .query_row(
+                "SELECT role FROM temporary_roles
+                 WHERE user_id = ?1 AND hall_id = ?2 AND expires_at > ?3",
+                params![
+                    user_id.to_string(),
+                    hall_id.to_string(),
+                    Utc::now().to_rfc3339()
+                ],
+                |row| row.get::<_, u8>(0),
+            )
+            .optional()?
+            .map(role_from_u8);
+
+        Ok(Some(
+            granted.map_or(membership.role, |g| g.max(membership.role)),
+        ))
     }
 
     /// Set hall host (for host election persistence)
@@ -286,3 +617,348 @@ impl<'a> HallStore<'a> {
         Ok(username)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ParlorState, User};
+    use crate::storage::Database;
+
+    #[test]
+    fn test_active_parlor_follows_state() {
+        let db = Database::open_in_memory().unwrap();
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+        let hall = Hall::new("Test Hall".into(), user.id);
+        db.halls().create(&hall).unwrap();
+
+        let parlor = ParlorId::new();
+        let mut state = ParlorState::Inactive;
+        state.begin_activation(parlor).unwrap();
+        state.complete_activation().unwrap();
+        db.halls()
+            .set_active_parlor(hall.id, state.active_parlor())
+            .unwrap();
+
+        let stored = db.halls().find_by_id(hall.id).unwrap().unwrap();
+        assert_eq!(stored.active_parlor, Some(parlor));
+
+        state.begin_deactivation().unwrap();
+        db.halls()
+            .set_active_parlor(hall.id, state.active_parlor())
+            .unwrap();
+        let stored = db.halls().find_by_id(hall.id).unwrap().unwrap();
+        assert_eq!(stored.active_parlor, None);
+    }
+
+    /// Create a Hall owned by a Builder with one Agent member (test helper)
+    fn setup(db: &Database) -> (Uuid, Uuid) {
+        let owner = User::new("alice".into(), "hash".into());
+        let agent = User::new("bob".into(), "hash".into());
+        db.users().create(&owner).unwrap();
+        db.users().create(&agent).unwrap();
+
+        let hall = Hall::new("Test Hall".into(), owner.id);
+        db.halls().create(&hall).unwrap();
+        db.halls()
+            .add_member(&Membership::new(owner.id, hall.id, HallRole::HallBuilder))
+            .unwrap();
+        db.halls()
+            .add_member(&Membership::new(agent.id, hall.id, HallRole::HallAgent))
+            .unwrap();
+
+        (agent.id, hall.id)
+    }
+
+    #[test]
+    fn test_temporary_role_elevates_until_expiry() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+
+        db.halls()
+            .grant_temporary_role(
+                agent_id,
+                hall_id,
+                HallRole::HallPrefect,
+                Utc::now() + chrono::Duration::hours(1),
+            )
+            .unwrap();
+        assert_eq!(
+            db.halls().get_user_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallPrefect)
+        );
+
+        // An expired grant falls back to the base role
+        db.halls()
+            .grant_temporary_role(
+                agent_id,
+                hall_id,
+                HallRole::HallPrefect,
+                Utc::now() - chrono::Duration::seconds(1),
+            )
+            .unwrap();
+        assert_eq!(
+            db.halls().get_user_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallAgent)
+        );
+    }
+
+    #[test]
+    fn test_temporary_role_never_lowers_or_outlives_membership() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+        let later = Utc::now() + chrono::Duration::hours(1);
+
+        db.halls()
+            .grant_temporary_role(agent_id, hall_id, HallRole::HallFellow, later)
+            .unwrap();
+        assert_eq!(
+            db.halls().get_user_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallAgent)
+        );
+
+        assert!(matches!(
+            db.halls()
+                .grant_temporary_role(agent_id, hall_id, HallRole::HallBuilder, later),
+            Err(Error::InvalidOperation(_))
+        ));
+
+        db.halls()
+            .grant_temporary_role(agent_id, hall_id, HallRole::HallModerator, later)
+            .unwrap();
+        db.halls().remove_member(agent_id, hall_id).unwrap();
+        db.halls()
+            .add_member(&Membership::new(agent_id, hall_id, HallRole::HallFellow))
+            .unwrap();
+        assert_eq!(
+            db.halls().get_user_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallFellow)
+        );
+    }
+
+    #[test]
+    fn test_banned_user_cannot_rejoin_until_unbanned() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+        let owner_id = db.halls().find_by_id(hall_id).unwrap().unwrap().owner_id;
+
+        db.halls()
+            .ban_user(hall_id, agent_id, owner_id, Some("spam"))
+            .unwrap();
+        assert!(db.halls().is_banned(hall_id, agent_id).unwrap());
+        assert!(db
+            .halls()
+            .get_membership(agent_id, hall_id)
+            .unwrap()
+            .is_none());
+
+        let rejoin = Membership::new(agent_id, hall_id, HallRole::HallFellow);
+        assert!(matches!(db.halls().add_member(&rejoin), Err(Error::Banned)));
+
+        db.halls().unban_user(hall_id, agent_id).unwrap();
+        assert!(!db.halls().is_banned(hall_id, agent_id).unwrap());
+        db.halls().add_member(&rejoin).unwrap();
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        use crate::models::{SETTING_SLOW_MODE_SECS, SETTING_WELCOME_MESSAGE};
+
+        let db = Database::open_in_memory().unwrap();
+        let (_, hall_id) = setup(&db);
+
+        assert_eq!(
+            db.halls()
+                .get_setting::<u32>(hall_id, SETTING_SLOW_MODE_SECS)
+                .unwrap(),
+            None
+        );
+
+        db.halls()
+            .set_setting(hall_id, SETTING_SLOW_MODE_SECS, &10u32)
+            .unwrap();
+        db.halls()
+            .set_setting(hall_id, SETTING_WELCOME_MESSAGE, &"Welcome aboard")
+            .unwrap();
+        db.halls()
+            .set_setting(hall_id, SETTING_SLOW_MODE_SECS, &30u32)
+            .unwrap();
+
+        assert_eq!(
+            db.halls()
+                .get_setting::<u32>(hall_id, SETTING_SLOW_MODE_SECS)
+                .unwrap(),
+            Some(30)
+        );
+        assert_eq!(
+            db.halls()
+                .get_setting::<String>(hall_id, SETTING_WELCOME_MESSAGE)
+                .unwrap()
+                .as_deref(),
+            Some("Welcome aboard")
+        );
+
+        let settings = db.halls().list_settings(hall_id).unwrap();
+        assert_eq!(
+            settings,
+            vec![
+                (SETTING_SLOW_MODE_SECS.to_string(), serde_json::json!(30)),
+                (
+                    SETTING_WELCOME_MESSAGE.to_string(),
+                    serde_json::json!("Welcome aboard")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_kick_is_logged() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+        let owner_id = db.halls().find_by_id(hall_id).unwrap().unwrap().owner_id;
+
+        db.halls()
+            .kick_member(hall_id, agent_id, owner_id, Some("off topic"))
+            .unwrap();
+        assert!(db
+            .halls()
+            .get_membership(agent_id, hall_id)
+            .unwrap()
+            .is_none());
+
+        let log = db.halls().list_moderation_log(hall_id, 10).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].actor_id, owner_id);
+        assert_eq!(log[0].target_id, agent_id);
+        assert_eq!(log[0].action, ModerationAction::Kick);
+        assert_eq!(log[0].reason.as_deref(), Some("off topic"));
+    }
+
+    #[test]
+    fn test_moderation_log_is_newest_first_and_limited() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+        let owner_id = db.halls().find_by_id(hall_id).unwrap().unwrap().owner_id;
+
+        db.halls()
+            .log_moderation(hall_id, owner_id, agent_id, ModerationAction::Promote, None)
+            .unwrap();
+        db.halls()
+            .ban_user(hall_id, agent_id, owner_id, None)
+            .unwrap();
+
+        let log = db.halls().list_moderation_log(hall_id, 1).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].action, ModerationAction::Ban);
+        assert_eq!(
+            db.halls().list_moderation_log(hall_id, 10).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_member_and_online_counts() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+
+        assert_eq!(db.halls().member_count(hall_id).unwrap(), 2);
+        assert_eq!(db.halls().online_count(hall_id).unwrap(), 0);
+
+        db.halls()
+            .update_online_status(agent_id, hall_id, true)
+            .unwrap();
+        assert_eq!(db.halls().online_count(hall_id).unwrap(), 1);
+
+        db.halls()
+            .update_online_status(agent_id, hall_id, false)
+            .unwrap();
+        assert_eq!(db.halls().online_count(hall_id).unwrap(), 0);
+
+        db.halls().remove_member(agent_id, hall_id).unwrap();
+        assert_eq!(db.halls().member_count(hall_id).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_members_carry_display_color() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+        db.users()
+            .set_accent_color(agent_id, Some("#336699"))
+            .unwrap();
+
+        let members = db.halls().list_members(hall_id).unwrap();
+        let owner = members.iter().find(|m| m.user_id != agent_id).unwrap();
+        let agent = members.iter().find(|m| m.user_id == agent_id).unwrap();
+        assert_eq!(agent.color, "#336699");
+        assert_eq!(owner.color, display_color_for(owner.user_id, None));
+    }
+
+    #[test]
+    fn test_role_change_starts_from_base_and_revokes_grant() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+
+        db.halls()
+            .grant_temporary_role(
+                agent_id,
+                hall_id,
+                HallRole::HallPrefect,
+                Utc::now() + chrono::Duration::hours(1),
+            )
+            .unwrap();
+        assert_eq!(
+            db.halls().base_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallAgent)
+        );
+
+        // Member lists show the same effective role permission checks use
+        let members = db.halls().list_members(hall_id).unwrap();
+        let agent = members.iter().find(|m| m.user_id == agent_id).unwrap();
+        assert_eq!(agent.role, HallRole::HallPrefect);
+
+        db.halls()
+            .update_role(agent_id, hall_id, HallRole::HallFellow)
+            .unwrap();
+        assert_eq!(
+            db.halls().get_user_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallFellow)
+        );
+    }
+
+    #[test]
+    fn test_step_down_uses_base_role() {
+        let db = Database::open_in_memory().unwrap();
+        let (agent_id, hall_id) = setup(&db);
+        db.halls()
+            .grant_temporary_role(
+                agent_id,
+                hall_id,
+                HallRole::HallPrefect,
+                Utc::now() + chrono::Duration::hours(1),
+            )
+            .unwrap();
+
+        // "Stepping down" from the grant to Moderator would raise the base role
+        assert!(matches!(
+            db.halls()
+                .step_down(agent_id, hall_id, HallRole::HallModerator),
+            Err(Error::PermissionDenied(_))
+        ));
+        assert_eq!(
+            db.halls().base_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallAgent)
+        );
+
+        db.halls()
+            .step_down(agent_id, hall_id, HallRole::HallFellow)
+            .unwrap();
+        assert_eq!(
+            db.halls().base_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallFellow)
+        );
+        assert_eq!(
+            db.halls().get_user_role(agent_id, hall_id).unwrap(),
+            Some(HallRole::HallFellow)
+        );
+    }
+}
@@ -1,11 +1,12 @@
 //! Invite storage operations
 
+use chrono::Utc;
 use rusqlite::{params, Connection};
 use tracing::instrument;
 use uuid::Uuid;
 
 use super::parse::{parse_datetime, parse_datetime_opt, parse_uuid, role_from_u8, OptionalExt};
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::models::Invite;
 
 pub struct InviteStore<'a> {
@@ -20,6 +21,12 @@ impl<'a> InviteStore<'a> {
     /// Create a new invite
     #[instrument(skip(self, invite), fields(hall_id = %invite.hall_id, role = ?invite.role))]
     pub fn create(&self, invite: &Invite) -> Result<()> {
+        if invite.max_uses == Some(0) {
+            return Err(Error::InvalidOperation(
+                "Invite max uses must be at least 1".into(),
+            ));
+        }
+
         self.conn.execute(
             "INSERT INTO invites (id, hall_id, token, created_by, role, created_at, expires_at, max_uses, use_count, is_revoked)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
@@ -95,6 +102,41 @@ impl<'a> InviteStore<'a> {
         Ok(invites)
     }
 
+    /// Redeem an invite, consuming one use
+    ///
+    /// Fails if the invite is unknown, revoked, expired or has reached
+    /// `max_uses`. The use count is incremented by a single conditional
+    /// update, so two concurrent redemptions cannot both take the last use.
+    /// When the update matches nothing, the row is read back to report why.
+    #[instrument(skip(self, token))]
+    pub fn redeem(&self, token: &str) -> Result<Invite> {
+        let updated = self.conn.execute(
+            "UPDATE invites SET use_count = use_count + 1
+             WHERE token = ?1 AND is_revoked = 0
+               AND (expires_at IS NULL OR expires_at >= ?2)
+               AND (max_uses IS NULL OR use_count < max_uses)",
+            params![token, Utc::now().to_rfc3339()],
+        )?;
+
+        let invite = self
+            .find_by_token(token)?
+            .ok_or_else(|| Error::NotFound("Invite not found".into()))?;
+        if updated > 0 {
+            return Ok(invite);
+        }
+
+        if invite.is_revoked {
+            Err(Error::Invitation("Invite has been revoked".into()))
+        } else if invite
+            .expires_at
+            .is_some_and(|expires| Utc::now() > expires)
+        {
+            Err(Error::InviteExpired)
+        } else {
+            Err(Error::InviteExhausted)
+        }
+    }
+
     /// Increment use count
     #[instrument(skip(self))]
     pub fn increment_use_count(&self, invite_id: Uuid) -> Result<()> {
@@ -125,3 +167,118 @@ impl<'a> InviteStore<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Hall, HallRole, Membership, User};
+    use crate::storage::{Database, HallStore};
+
+    /// Create a Hall owned by a fresh user (test helper)
+    fn setup(db: &Database) -> (Uuid, Uuid) {
+        let user = User::new("owner".into(), "hash".into());
+        db.users().create(&user).unwrap();
+        let hall = Hall::new("Test Hall".into(), user.id);
+        db.halls().create(&hall).unwrap();
+        (user.id, hall.id)
+    }
+
+    #[test]
+    fn test_redeem_single_use_invite_twice() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let invite = Invite::new(hall_id, user_id, HallRole::HallAgent, "once".into()).single_use();
+        db.invites().create(&invite).unwrap();
+
+        let redeemed = db.invites().redeem("once").unwrap();
+        assert_eq!(redeemed.use_count, 1);
+
+        assert!(matches!(
+            db.invites().redeem("once"),
+            Err(Error::InviteExhausted)
+        ));
+        let stored = db.invites().find_by_token("once").unwrap().unwrap();
+        assert_eq!(stored.use_count, 1);
+    }
+
+    #[test]
+    fn test_redeem_rejects_revoked_and_expired() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let revoked = Invite::new(hall_id, user_id, HallRole::HallAgent, "revoked".into());
+        db.invites().create(&revoked).unwrap();
+        db.invites().revoke(revoked.id).unwrap();
+        assert!(matches!(
+            db.invites().redeem("revoked"),
+            Err(Error::Invitation(_))
+        ));
+
+        let expired =
+            Invite::new(hall_id, user_id, HallRole::HallAgent, "expired".into()).with_expiry(-1);
+        db.invites().create(&expired).unwrap();
+        assert!(matches!(
+            db.invites().redeem("expired"),
+            Err(Error::InviteExpired)
+        ));
+
+        assert!(matches!(
+            db.invites().redeem("missing"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_failed_redeem_reports_current_state() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let invite = Invite::new(hall_id, user_id, HallRole::HallAgent, "once".into()).single_use();
+        db.invites().create(&invite).unwrap();
+        db.invites().redeem("once").unwrap();
+
+        // Revoked after its last use, the invite reports the revocation
+        db.invites().revoke(invite.id).unwrap();
+        assert!(matches!(
+            db.invites().redeem("once"),
+            Err(Error::Invitation(_))
+        ));
+        let stored = db.invites().find_by_token("once").unwrap().unwrap();
+        assert_eq!(stored.use_count, 1);
+    }
+
+    #[test]
+    fn test_zero_max_uses_rejected_at_creation() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let invite =
+            Invite::new(hall_id, user_id, HallRole::HallAgent, "never".into()).with_max_uses(0);
+        assert!(db.invites().create(&invite).is_err());
+    }
+
+    #[test]
+    fn test_banned_join_does_not_consume_invite() {
+        let db = Database::open_in_memory().unwrap();
+        let (owner_id, hall_id) = setup(&db);
+        let banned = User::new("mallory".into(), "hash".into());
+        db.users().create(&banned).unwrap();
+        db.halls()
+            .ban_user(hall_id, banned.id, owner_id, None)
+            .unwrap();
+
+        let invite =
+            Invite::new(hall_id, owner_id, HallRole::HallAgent, "once".into()).single_use();
+        db.invites().create(&invite).unwrap();
+
+        let joined = db.transaction(|tx| {
+            let invite = InviteStore::new(tx).redeem("once")?;
+            HallStore::new(tx).add_member(&Membership::new(banned.id, hall_id, invite.role))
+        });
+        assert!(matches!(joined, Err(Error::Banned)));
+
+        let stored = db.invites().find_by_token("once").unwrap().unwrap();
+        assert_eq!(stored.use_count, 0);
+    }
+}
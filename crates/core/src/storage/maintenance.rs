@@ -0,0 +1,122 @@
+//! Startup housekeeping
+//!
+//! Expired rows are ignored by reads but never removed on their own; this
+//! sweeps them in one pass so the app can run it once at launch.
+
+use chrono::Utc;
+use rusqlite::params;
+use tracing::{info, instrument};
+
+use super::{Database, UserStore};
+use crate::error::Result;
+
+/// Share of free pages above which maintenance compacts the database
+const VACUUM_FREE_PAGE_RATIO: f64 = 0.25;
+
+/// What a maintenance run removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    pub expired_sessions: u64,
+    pub expired_invites: u64,
+    pub expired_temporary_roles: u64,
+    /// Whether the database file was compacted
+    pub vacuumed: bool,
+}
+
+impl Database {
+    /// Delete expired sessions, invites and temporary role grants
+    ///
+    /// Runs `VACUUM` afterwards if more than a quarter of the file is free
+    /// pages. Deletions are committed together before compacting, since
+    /// `VACUUM` cannot run inside a transaction.
+    #[instrument(skip(self))]
+    pub fn run_maintenance(&self) -> Result<MaintenanceReport> {
+        let now = Utc::now().to_rfc3339();
+
+        let mut report = self.transaction(|tx| {
+            let expired_sessions = UserStore::new(tx).cleanup_expired_sessions()?;
+            let expired_invites = tx.execute(
+                "DELETE FROM invites WHERE expires_at IS NOT NULL AND expires_at < ?1",
+                params![now],
+            )?;
+            let expired_temporary_roles = tx.execute(
+                "DELETE FROM temporary_roles WHERE expires_at <= ?1",
+                params![now],
+            )?;
+
+            Ok(MaintenanceReport {
+                expired_sessions,
+                expired_invites: expired_invites as u64,
+                expired_temporary_roles: expired_temporary_roles as u64,
+                vacuumed: false,
+            })
+        })?;
+
+        let page_count: i64 = self
+            .conn
+            .query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let free_pages: i64 = self
+            .conn
+            .query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        if page_count > 0 && free_pages as f64 / page_count as f64 > VACUUM_FREE_PAGE_RATIO {
+            self.conn.execute_batch("VACUUM")?;
+            report.vacuumed = true;
+        }
+
+        info!(?report, "Maintenance complete");
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Hall, HallRole, Invite, Membership, Session, User};
+
+    #[test]
+    fn test_maintenance_removes_expired_rows() {
+        let db = Database::open_in_memory().unwrap();
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+        let hall = Hall::new("Test Hall".into(), user.id);
+        db.halls().create(&hall).unwrap();
+
+        let expired = Session::new(user.id, -1);
+        let live = Session::new(user.id, 24);
+        db.users().create_session(&expired).unwrap();
+        db.users().create_session(&live).unwrap();
+
+        let stale =
+            Invite::new(hall.id, user.id, HallRole::HallFellow, "stale".into()).with_expiry(-1);
+        let open = Invite::new(hall.id, user.id, HallRole::HallFellow, "open".into());
+        db.invites().create(&stale).unwrap();
+        db.invites().create(&open).unwrap();
+
+        let guest = User::new("bob".into(), "hash".into());
+        db.users().create(&guest).unwrap();
+        db.halls()
+            .add_member(&Membership::new(guest.id, hall.id, HallRole::HallFellow))
+            .unwrap();
+        db.halls()
+            .grant_temporary_role(
+                guest.id,
+                hall.id,
+                HallRole::HallModerator,
+                Utc::now() - chrono::Duration::minutes(1),
+            )
+            .unwrap();
+
+        let report = db.run_maintenance().unwrap();
+        assert_eq!(report.expired_sessions, 1);
+        assert_eq!(report.expired_invites, 1);
+        assert_eq!(report.expired_temporary_roles, 1);
+        assert!(db.users().find_valid_session(live.id).unwrap().is_some());
+        assert!(db.invites().find_by_token("open").unwrap().is_some());
+
+        // Nothing left to sweep the second time
+        let report = db.run_maintenance().unwrap();
+        assert_eq!(report.expired_sessions, 0);
+        assert_eq!(report.expired_invites, 0);
+        assert_eq!(report.expired_temporary_roles, 0);
+    }
+}
@@ -1,13 +1,40 @@
 //! Message storage operations
 
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection};
-use tracing::instrument;
+use tracing::{instrument, warn};
 use uuid::Uuid;
 
 use super::parse::{parse_datetime, parse_datetime_opt, parse_uuid, role_from_u8, OptionalExt};
-use crate::error::Result;
-use crate::models::{HallRole, Message, MessageDisplay};
+use crate::error::{Error, Result};
+use crate::models::{display_color_for, HallRole, Message, MessageDisplay};
+
+/// Latest page of a Hall's messages, newest first
+const LIST_LATEST_QUERY: &str =
+    "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at, m.is_pinned, u.is_bot,
+       m.reply_to, m.sender_id, u.accent_color
+     FROM messages m
+     INNER JOIN users u ON u.id = m.sender_id
+     LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
+     WHERE m.hall_id = ?1 AND m.is_deleted = 0
+     ORDER BY m.created_at DESC
+     LIMIT ?2";
+
+/// Page of a Hall's messages strictly older than a cursor, newest first
+///
+/// Served by `idx_messages_hall_created`; the cursor is exclusive so
+/// consecutive pages never repeat a row.
+const LIST_BEFORE_QUERY: &str =
+    "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at, m.is_pinned, u.is_bot,
+       m.reply_to, m.sender_id, u.accent_color
+     FROM messages m
+     INNER JOIN users u ON u.id = m.sender_id
+     LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
+     WHERE m.hall_id = ?1 AND m.is_deleted = 0 AND m.created_at < ?2
+     ORDER BY m.created_at DESC
+     LIMIT ?3";
 
 pub struct MessageStore<'a> {
     conn: &'a Connection,
@@ -21,9 +48,10 @@ impl<'a> MessageStore<'a> {
     /// Create a new message
     #[instrument(skip(self, message), fields(hall_id = %message.hall_id, sender_id = %message.sender_id))]
     pub fn create(&self, message: &Message) -> Result<()> {
+        Self::check_reply_to(self.conn, message)?;
         self.conn.execute(
-            "INSERT INTO messages (id, hall_id, sender_id, content, created_at, edited_at, is_deleted)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT INTO messages (id, hall_id, sender_id, content, created_at, edited_at, is_deleted, deleted_at, reply_to)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![
                 message.id.to_string(),
                 message.hall_id.to_string(),
@@ -32,16 +60,106 @@ impl<'a> MessageStore<'a> {
                 message.created_at.to_rfc3339(),
                 message.edited_at.map(|t| t.to_rfc3339()),
                 message.is_deleted as i32,
+                message.deleted_at.map(|t| t.to_rfc3339()),
+                message.reply_to.map(|id| id.to_string()),
             ],
         )?;
         Ok(())
     }
 
+    /// Insert a batch of messages in a single transaction
+    ///
+    /// Messages whose id already exists are skipped. Replies may point at a
+    /// message earlier or later in the same batch; a reply whose parent is
+    /// neither in the batch nor stored is kept without its `reply_to`.
+    /// Returns the number of newly inserted rows.
+    #[instrument(skip(self, messages), fields(count = messages.len()))]
+    pub fn create_many(&self, messages: &[Message]) -> Result<usize> {
+        let batch: HashMap<Uuid, Uuid> = messages.iter().map(|m| (m.id, m.hall_id)).collect();
+        let tx = self.conn.unchecked_transaction()?;
+        // Parents later in the batch aren't inserted yet when their replies are
+        tx.pragma_update(None, "defer_foreign_keys", true)?;
+        let mut inserted = 0;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO messages (id, hall_id, sender_id, content, created_at, edited_at, is_deleted, deleted_at, reply_to)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+
+            for message in messages {
+                let reply_to = match message.reply_to {
+                    Some(parent_id) => {
+                        let parent_hall = match batch.get(&parent_id) {
+                            Some(hall_id) => Some(*hall_id),
+                            None => Self::parent_hall(&tx, parent_id)?,
+                        };
+                        match parent_hall {
+                            Some(hall_id) if hall_id == message.hall_id => Some(parent_id),
+                            Some(_) => {
+                                return Err(Error::InvalidOperation(
+                                    "Cannot reply to a message in another Hall".into(),
+                                ))
+                            }
+                            None => {
+                                warn!(message_id = %message.id, %parent_id, "Dropping reply to missing message");
+                                None
+                            }
+                        }
+                    }
+                    None => None,
+                };
+
+                inserted += stmt.execute(params![
+                    message.id.to_string(),
+                    message.hall_id.to_string(),
+                    message.sender_id.to_string(),
+                    message.content,
+                    message.created_at.to_rfc3339(),
+                    message.edited_at.map(|t| t.to_rfc3339()),
+                    message.is_deleted as i32,
+                    message.deleted_at.map(|t| t.to_rfc3339()),
+                    reply_to.map(|id| id.to_string()),
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// Ensure a reply points at an existing message in the same Hall
+    fn check_reply_to(conn: &Connection, message: &Message) -> Result<()> {
+        let Some(parent_id) = message.reply_to else {
+            return Ok(());
+        };
+
+        match Self::parent_hall(conn, parent_id)? {
+            Some(hall_id) if hall_id == message.hall_id => Ok(()),
+            Some(_) => Err(Error::InvalidOperation(
+                "Cannot reply to a message in another Hall".into(),
+            )),
+            None => Err(Error::NotFound(format!("Message {}", parent_id))),
+        }
+    }
+
+    /// Hall a stored message belongs to, if it exists
+    fn parent_hall(conn: &Connection, parent_id: Uuid) -> Result<Option<Uuid>> {
+        let hall_id = conn
+            .query_row(
+                "SELECT hall_id FROM messages WHERE id = ?1",
+                params![parent_id.to_string()],
+                |row| parse_uuid(&row.get::<_, String>(0)?),
+            )
+            .optional()?;
+        Ok(hall_id)
+    }
+
     /// Get message by ID
     #[instrument(skip(self))]
     pub fn find_by_id(&self, id: Uuid) -> Result<Option<Message>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, hall_id, sender_id, content, created_at, edited_at, is_deleted
+            "SELECT id, hall_id, sender_id, content, created_at, edited_at, is_deleted, deleted_at, reply_to
              FROM messages WHERE id = ?1",
         )?;
 
@@ -55,6 +173,11 @@ impl<'a> MessageStore<'a> {
                     created_at: parse_datetime(&row.get::<_, String>(4)?)?,
                     edited_at: parse_datetime_opt(row.get::<_, Option<String>>(5)?)?,
                     is_deleted: row.get::<_, i32>(6)? != 0,
+                    deleted_at: parse_datetime_opt(row.get::<_, Option<String>>(7)?)?,
+                    reply_to: row
+                        .get::<_, Option<String>>(8)?
+                        .map(|id| parse_uuid(&id))
+                        .transpose()?,
                 })
             })
             .optional()?;
@@ -71,21 +194,9 @@ impl<'a> MessageStore<'a> {
         before: Option<DateTime<Utc>>,
     ) -> Result<Vec<MessageDisplay>> {
         let query = if before.is_some() {
-            "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at
-             FROM messages m
-             INNER JOIN users u ON u.id = m.sender_id
-             LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
-             WHERE m.hall_id = ?1 AND m.is_deleted = 0 AND m.created_at < ?2
-             ORDER BY m.created_at DESC
-             LIMIT ?3"
+            LIST_BEFORE_QUERY
         } else {
-            "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at
-             FROM messages m
-             INNER JOIN users u ON u.id = m.sender_id
-             LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
-             WHERE m.hall_id = ?1 AND m.is_deleted = 0
-             ORDER BY m.created_at DESC
-             LIMIT ?2"
+            LIST_LATEST_QUERY
         };
 
         let mut stmt = self.conn.prepare(query)?;
@@ -107,6 +218,71 @@ impl<'a> MessageStore<'a> {
         // Reverse to get chronological order
         let mut messages = messages;
         messages.reverse();
+
+        self.load_reactions(&mut messages)?;
+
+        Ok(messages)
+    }
+
+    /// List messages for a Hall sent within `[start, end)`, oldest first
+    ///
+    /// Unlike [`list_for_hall`](Self::list_for_hall) this is not capped, so
+    /// callers summarising a period see every message in it.
+    #[instrument(skip(self))]
+    pub fn list_for_hall_between(
+        &self,
+        hall_id: Uuid,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<MessageDisplay>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at, m.is_pinned, u.is_bot,
+               m.reply_to, m.sender_id, u.accent_color
+             FROM messages m
+             INNER JOIN users u ON u.id = m.sender_id
+             LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
+             WHERE m.hall_id = ?1 AND m.is_deleted = 0
+               AND m.created_at >= ?2 AND m.created_at < ?3
+             ORDER BY m.created_at ASC",
+        )?;
+
+        let mut messages = stmt
+            .query_map(
+                params![hall_id.to_string(), start.to_rfc3339(), end.to_rfc3339()],
+                Self::map_message_display,
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        self.load_reactions(&mut messages)?;
+
+        Ok(messages)
+    }
+
+    /// List a message followed by its direct replies, oldest reply first
+    ///
+    /// Deleted replies are left out. Returns an empty list if the root
+    /// message does not exist or was deleted.
+    #[instrument(skip(self))]
+    pub fn list_thread(&self, root_id: Uuid) -> Result<Vec<MessageDisplay>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at, m.is_pinned, u.is_bot,
+               m.reply_to, m.sender_id, u.accent_color
+             FROM messages m
+             INNER JOIN users u ON u.id = m.sender_id
+             LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
+             WHERE (m.id = ?1 OR m.reply_to = ?1) AND m.is_deleted = 0
+             ORDER BY m.id != ?1, m.created_at ASC",
+        )?;
+
+        let mut messages = stmt
+            .query_map(params![root_id.to_string()], Self::map_message_display)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if messages.first().map(|m| m.id) != Some(root_id) {
+            return Ok(Vec::new());
+        }
+
+        self.load_reactions(&mut messages)?;
+
         Ok(messages)
     }
 
@@ -121,9 +297,66 @@ impl<'a> MessageStore<'a> {
             content: row.get(3)?,
             timestamp: parse_datetime(&row.get::<_, String>(4)?)?,
             is_edited: row.get::<_, Option<String>>(5)?.is_some(),
+            is_pinned: row.get::<_, i32>(6)? != 0,
+            sender_is_bot: row.get::<_, i32>(7)? != 0,
+            reply_to: row
+                .get::<_, Option<String>>(8)?
+                .map(|id| parse_uuid(&id))
+                .transpose()?,
+            sender_color: display_color_for(
+                parse_uuid(&row.get::<_, String>(9)?)?,
+                row.get::<_, Option<String>>(10)?.as_deref(),
+            ),
+            reactions: Vec::new(),
         })
     }
 
+    /// Search message content in a Hall, best matches first
+    ///
+    /// Each word in `query` is matched case-insensitively as a prefix, so
+    /// "ship" finds "shipped". Returns an empty list for a blank query.
+    #[instrument(skip(self, query))]
+    pub fn search(&self, hall_id: Uuid, query: &str, limit: u32) -> Result<Vec<MessageDisplay>> {
+        let match_expr = Self::fts_query(query);
+        if match_expr.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at, m.is_pinned, u.is_bot,
+               m.reply_to, m.sender_id, u.accent_color
+             FROM messages_fts
+             INNER JOIN messages m ON m.rowid = messages_fts.rowid
+             INNER JOIN users u ON u.id = m.sender_id
+             LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
+             WHERE messages_fts MATCH ?1 AND m.hall_id = ?2 AND m.is_deleted = 0
+             ORDER BY messages_fts.rank
+             LIMIT ?3",
+        )?;
+
+        let mut messages = stmt
+            .query_map(
+                params![match_expr, hall_id.to_string(), limit],
+                Self::map_message_display,
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        self.load_reactions(&mut messages)?;
+
+        Ok(messages)
+    }
+
+    /// Build an FTS5 match expression from free-form user input
+    ///
+    /// Words are quoted so user text can never be parsed as FTS syntax.
+    fn fts_query(query: &str) -> String {
+        query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{}\"*", word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     /// Update message content
     #[instrument(skip(self, new_content))]
     pub fn update_content(&self, message_id: Uuid, new_content: &str) -> Result<()> {
@@ -135,15 +368,189 @@ impl<'a> MessageStore<'a> {
     }
 
     /// Soft delete message
+    ///
+    /// The row stays in place (hidden from listings) until purged by
+    /// `purge_deleted_older_than`.
     #[instrument(skip(self))]
     pub fn delete(&self, message_id: Uuid) -> Result<()> {
         self.conn.execute(
-            "UPDATE messages SET is_deleted = 1 WHERE id = ?1",
-            params![message_id.to_string()],
+            "UPDATE messages SET is_deleted = 1, deleted_at = ?1 WHERE id = ?2 AND is_deleted = 0",
+            params![Utc::now().to_rfc3339(), message_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Permanently remove messages soft-deleted before `cutoff`
+    ///
+    /// Returns the number of rows purged.
+    #[instrument(skip(self))]
+    pub fn purge_deleted_older_than(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let count = self.conn.execute(
+            "DELETE FROM messages WHERE is_deleted = 1 AND deleted_at < ?1",
+            params![cutoff.to_rfc3339()],
+        )?;
+        Ok(count as u64)
+    }
+
+    /// Pin or unpin a message
+    ///
+    /// Callers must check `HallAction::PinMessages` before calling this.
+    #[instrument(skip(self))]
+    pub fn set_pinned(&self, message_id: Uuid, pinned: bool) -> Result<()> {
+        let pinned_at = pinned.then(|| Utc::now().to_rfc3339());
+        self.conn.execute(
+            "UPDATE messages SET is_pinned = ?1, pinned_at = ?2 WHERE id = ?3",
+            params![pinned as i32, pinned_at, message_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// List pinned messages for a Hall in the order they were pinned
+    #[instrument(skip(self))]
+    pub fn list_pinned(&self, hall_id: Uuid) -> Result<Vec<MessageDisplay>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT m.id, u.username, mb.role, m.content, m.created_at, m.edited_at, m.is_pinned, u.is_bot,
+               m.reply_to, m.sender_id, u.accent_color
+             FROM messages m
+             INNER JOIN users u ON u.id = m.sender_id
+             LEFT JOIN memberships mb ON mb.user_id = m.sender_id AND mb.hall_id = m.hall_id
+             WHERE m.hall_id = ?1 AND m.is_deleted = 0 AND m.is_pinned = 1
+             ORDER BY m.pinned_at, m.rowid",
+        )?;
+
+        let mut messages = stmt
+            .query_map(params![hall_id.to_string()], Self::map_message_display)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        self.load_reactions(&mut messages)?;
+
+        Ok(messages)
+    }
+
+    /// Add a reaction (no-op if the user already reacted with this emoji)
+    #[instrument(skip(self))]
+    pub fn add_reaction(&self, message_id: Uuid, user_id: Uuid, emoji: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO reactions (message_id, user_id, emoji, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                message_id.to_string(),
+                user_id.to_string(),
+                emoji,
+                Utc::now().to_rfc3339(),
+            ],
         )?;
         Ok(())
     }
 
+    /// Remove a user's reaction
+    #[instrument(skip(self))]
+    pub fn remove_reaction(&self, message_id: Uuid, user_id: Uuid, emoji: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM reactions WHERE message_id = ?1 AND user_id = ?2 AND emoji = ?3",
+            params![message_id.to_string(), user_id.to_string(), emoji],
+        )?;
+        Ok(())
+    }
+
+    /// List reactions on a message as (emoji, count), in first-reacted order
+    #[instrument(skip(self))]
+    pub fn list_reactions(&self, message_id: Uuid) -> Result<Vec<(String, u32)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT emoji, COUNT(*) FROM reactions
+             WHERE message_id = ?1
+             GROUP BY emoji
+             ORDER BY MIN(created_at), emoji",
+        )?;
+
+        let reactions = stmt
+            .query_map(params![message_id.to_string()], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(reactions)
+    }
+
+    /// Fill in reactions for a page of messages with a single query
+    fn load_reactions(&self, messages: &mut [MessageDisplay]) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let ids = serde_json::to_string(
+            &messages
+                .iter()
+                .map(|m| m.id.to_string())
+                .collect::<Vec<_>>(),
+        )?;
+        let mut stmt = self.conn.prepare(
+            "SELECT message_id, emoji, COUNT(*) FROM reactions
+             WHERE message_id IN (SELECT value FROM json_each(?1))
+             GROUP BY message_id, emoji
+             ORDER BY message_id, MIN(created_at), emoji",
+        )?;
+
+        let mut by_message: HashMap<String, Vec<(String, u32)>> = HashMap::new();
+        let rows = stmt.query_map(params![ids], |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        for row in rows {
+            let (message_id, emoji, count) = row?;
+            by_message
+                .entry(message_id)
+                .or_default()
+                .push((emoji, count));
+        }
+
+        for message in messages {
+            message.reactions = by_message
+                .remove(&message.id.to_string())
+                .unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    /// When a user last posted in a Hall, including since-deleted messages
+    #[instrument(skip(self))]
+    pub fn last_message_at(&self, hall_id: Uuid, user_id: Uuid) -> Result<Option<DateTime<Utc>>> {
+        let last: Option<String> = self.conn.query_row(
+            "SELECT MAX(created_at) FROM messages WHERE hall_id = ?1 AND sender_id = ?2",
+            params![hall_id.to_string(), user_id.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(parse_datetime_opt(last)?)
+    }
+
+    /// Enforce slow mode before a user posts
+    ///
+    /// Fails with [`Error::SlowMode`] if the user posted less than
+    /// `slow_mode_secs` ago. Moderators and above are exempt, and `0`
+    /// disables slow mode.
+    #[instrument(skip(self))]
+    pub fn can_send_now(
+        &self,
+        hall_id: Uuid,
+        user_id: Uuid,
+        role: HallRole,
+        slow_mode_secs: u32,
+    ) -> Result<()> {
+        if slow_mode_secs == 0 || role >= HallRole::HallModerator {
+            return Ok(());
+        }
+
+        let Some(last) = self.last_message_at(hall_id, user_id)? else {
+            return Ok(());
+        };
+        let remaining = last + chrono::Duration::seconds(slow_mode_secs as i64) - Utc::now();
+        if remaining > chrono::Duration::zero() {
+            // Round up so "try again in 0s" is never shown
+            let retry_after = (remaining.num_milliseconds() as u64).div_ceil(1000);
+            return Err(Error::SlowMode { retry_after });
+        }
+
+        Ok(())
+    }
+
     /// Get message count for Hall
     #[instrument(skip(self))]
     pub fn count_for_hall(&self, hall_id: Uuid) -> Result<u64> {
@@ -154,4 +561,609 @@ impl<'a> MessageStore<'a> {
         )?;
         Ok(count as u64)
     }
+
+    /// Sequence of the newest message in a Hall, or 0 if it has none
+    ///
+    /// Every message gets the next sequence of its Hall when it is stored
+    /// locally. Sequences are never reused, even after a purge, but they
+    /// only compare meaningfully within one database.
+    #[instrument(skip(self))]
+    pub fn latest_sequence(&self, hall_id: Uuid) -> Result<u64> {
+        let sequence: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT last_sequence FROM hall_sequences WHERE hall_id = ?1",
+                params![hall_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(sequence.unwrap_or(0) as u64)
+    }
+
+    /// Record that a user has read a Hall up to `sequence`
+    ///
+    /// The marker only moves forward; marking an older sequence is a no-op.
+    #[instrument(skip(self))]
+    pub fn mark_read(&self, user_id: Uuid, hall_id: Uuid, sequence: u64) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO read_markers (user_id, hall_id, last_read_sequence)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(user_id, hall_id) DO UPDATE
+             SET last_read_sequence = MAX(last_read_sequence, ?3)",
+            params![user_id.to_string(), hall_id.to_string(), sequence as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Count messages in a Hall the user has not read yet
+    ///
+    /// A message is unread if its sequence is past the read marker. The
+    /// user's own messages never count as unread.
+    #[instrument(skip(self))]
+    pub fn unread_count(&self, user_id: Uuid, hall_id: Uuid) -> Result<u64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM messages m
+             LEFT JOIN read_markers r ON r.user_id = ?1 AND r.hall_id = m.hall_id
+             WHERE m.hall_id = ?2 AND m.is_deleted = 0 AND m.sender_id != ?1
+               AND m.sequence > COALESCE(r.last_read_sequence, 0)",
+            params![user_id.to_string(), hall_id.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Hall, Membership, User};
+    use crate::storage::Database;
+
+    /// Create a user and a Hall they belong to (test helper)
+    fn setup(db: &Database) -> (Uuid, Uuid) {
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+
+        let hall = Hall::new("Test Hall".into(), user.id);
+        db.halls().create(&hall).unwrap();
+        db.halls()
+            .add_member(&Membership::new(user.id, hall.id, HallRole::HallBuilder))
+            .unwrap();
+
+        (user.id, hall.id)
+    }
+
+    #[test]
+    fn test_search_matches_prefix() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let shipped = Message::new(hall_id, user_id, "We shipped the release".into());
+        db.messages().create(&shipped).unwrap();
+        db.messages()
+            .create(&Message::new(hall_id, user_id, "Lunch at noon?".into()))
+            .unwrap();
+
+        let results = db.messages().search(hall_id, "ship", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, shipped.id);
+
+        // Case-insensitive
+        let results = db.messages().search(hall_id, "SHIP", 10).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_search_tracks_edits_and_deletes() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let message = Message::new(hall_id, user_id, "draft notes".into());
+        db.messages().create(&message).unwrap();
+
+        db.messages()
+            .update_content(message.id, "final notes")
+            .unwrap();
+        assert!(db
+            .messages()
+            .search(hall_id, "draft", 10)
+            .unwrap()
+            .is_empty());
+        assert_eq!(db.messages().search(hall_id, "final", 10).unwrap().len(), 1);
+
+        db.messages().delete(message.id).unwrap();
+        assert!(db
+            .messages()
+            .search(hall_id, "final", 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_search_ignores_fts_syntax() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        db.messages()
+            .create(&Message::new(hall_id, user_id, "status: done".into()))
+            .unwrap();
+
+        assert_eq!(
+            db.messages().search(hall_id, "status:", 10).unwrap().len(),
+            1
+        );
+        assert!(db
+            .messages()
+            .search(hall_id, "\"*()", 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reactions_aggregate() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+        let bob = User::new("bob".into(), "hash".into());
+        db.users().create(&bob).unwrap();
+
+        let message = Message::new(hall_id, user_id, "ship it".into());
+        db.messages().create(&message).unwrap();
+
+        db.messages()
+            .add_reaction(message.id, user_id, "👍")
+            .unwrap();
+        db.messages()
+            .add_reaction(message.id, bob.id, "👍")
+            .unwrap();
+        db.messages()
+            .add_reaction(message.id, bob.id, "🎉")
+            .unwrap();
+        // Duplicate reaction is ignored
+        db.messages()
+            .add_reaction(message.id, bob.id, "👍")
+            .unwrap();
+
+        let reactions = db.messages().list_reactions(message.id).unwrap();
+        assert_eq!(
+            reactions,
+            vec![("👍".to_string(), 2), ("🎉".to_string(), 1)]
+        );
+
+        let quiet = Message::new(hall_id, user_id, "no reactions here".into());
+        db.messages().create(&quiet).unwrap();
+        db.messages().set_pinned(message.id, true).unwrap();
+
+        // Every listing carries reactions, loaded per page
+        let listed = db.messages().list_for_hall(hall_id, 10, None).unwrap();
+        let shipped = listed.iter().find(|m| m.id == message.id).unwrap();
+        assert_eq!(shipped.reactions, reactions);
+        let quiet_listed = listed.iter().find(|m| m.id == quiet.id).unwrap();
+        assert!(quiet_listed.reactions.is_empty());
+        assert_eq!(
+            db.messages().search(hall_id, "ship", 10).unwrap()[0].reactions,
+            reactions
+        );
+        assert_eq!(
+            db.messages().list_pinned(hall_id).unwrap()[0].reactions,
+            reactions
+        );
+
+        db.messages()
+            .remove_reaction(message.id, bob.id, "👍")
+            .unwrap();
+        let reactions = db.messages().list_reactions(message.id).unwrap();
+        assert_eq!(reactions[0], ("👍".to_string(), 1));
+    }
+
+    #[test]
+    fn test_reactions_cascade_on_delete() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let message = Message::new(hall_id, user_id, "ship it".into());
+        db.messages().create(&message).unwrap();
+        db.messages()
+            .add_reaction(message.id, user_id, "👍")
+            .unwrap();
+
+        db.conn
+            .execute(
+                "DELETE FROM messages WHERE id = ?1",
+                params![message.id.to_string()],
+            )
+            .unwrap();
+
+        let count: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM reactions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_pinned_messages_in_pin_order() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let first = Message::new(hall_id, user_id, "first".into());
+        let second = Message::new(hall_id, user_id, "second".into());
+        let third = Message::new(hall_id, user_id, "third".into());
+        for message in [&first, &second, &third] {
+            db.messages().create(message).unwrap();
+        }
+
+        // Pin out of creation order
+        db.messages().set_pinned(third.id, true).unwrap();
+        db.messages().set_pinned(first.id, true).unwrap();
+
+        let pinned = db.messages().list_pinned(hall_id).unwrap();
+        let ids: Vec<Uuid> = pinned.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![third.id, first.id]);
+        assert!(pinned.iter().all(|m| m.is_pinned));
+
+        let listed = db.messages().list_for_hall(hall_id, 10, None).unwrap();
+        let second_listed = listed.iter().find(|m| m.id == second.id).unwrap();
+        assert!(!second_listed.is_pinned);
+
+        db.messages().set_pinned(third.id, false).unwrap();
+        let pinned = db.messages().list_pinned(hall_id).unwrap();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].id, first.id);
+    }
+
+    #[test]
+    fn test_soft_delete_then_purge() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let kept = Message::new(hall_id, user_id, "kept".into());
+        let deleted = Message::new(hall_id, user_id, "deleted".into());
+        db.messages().create(&kept).unwrap();
+        db.messages().create(&deleted).unwrap();
+
+        db.messages().delete(deleted.id).unwrap();
+
+        // Soft-deleted rows are hidden but still stored
+        let listed = db.messages().list_for_hall(hall_id, 10, None).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, kept.id);
+        let stored = db.messages().find_by_id(deleted.id).unwrap().unwrap();
+        assert!(stored.is_deleted);
+        assert!(stored.deleted_at.is_some());
+
+        // Nothing is old enough to purge yet
+        let cutoff = Utc::now() - chrono::Duration::days(30);
+        assert_eq!(db.messages().purge_deleted_older_than(cutoff).unwrap(), 0);
+
+        let cutoff = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(db.messages().purge_deleted_older_than(cutoff).unwrap(), 1);
+        assert!(db.messages().find_by_id(deleted.id).unwrap().is_none());
+        assert!(db.messages().find_by_id(kept.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_create_many_skips_duplicates() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let batch: Vec<Message> = (0..500)
+            .map(|i| Message::new(hall_id, user_id, format!("message {}", i)))
+            .collect();
+
+        // 50 of the batch already exist locally
+        for message in &batch[..50] {
+            db.messages().create(message).unwrap();
+        }
+
+        let inserted = db.messages().create_many(&batch).unwrap();
+        assert_eq!(inserted, 450);
+        assert_eq!(db.messages().count_for_hall(hall_id).unwrap(), 500);
+    }
+
+    #[test]
+    fn test_list_between_is_half_open() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let start = Utc::now() - chrono::Duration::hours(2);
+        let end = start + chrono::Duration::hours(1);
+        let at = |time: DateTime<Utc>, content: &str| {
+            let mut message = Message::new(hall_id, user_id, content.into());
+            message.created_at = time;
+            db.messages().create(&message).unwrap();
+        };
+
+        at(start - chrono::Duration::seconds(1), "before");
+        at(start, "first");
+        at(start + chrono::Duration::minutes(30), "middle");
+        at(end, "after");
+
+        let window = db
+            .messages()
+            .list_for_hall_between(hall_id, start, end)
+            .unwrap();
+        let contents: Vec<_> = window.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["first", "middle"]);
+    }
+
+    #[test]
+    fn test_bot_messages_are_flagged() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+        let bot = db.users().ensure_bot_user("archivist").unwrap();
+
+        db.messages()
+            .create(&Message::new(hall_id, user_id, "hello".into()))
+            .unwrap();
+        db.messages()
+            .create(&Message::new(hall_id, bot.id, "Daily archive ready".into()))
+            .unwrap();
+
+        let messages = db.messages().list_for_hall(hall_id, 10, None).unwrap();
+        let flags: Vec<_> = messages.iter().map(|m| m.sender_is_bot).collect();
+        assert_eq!(flags, [false, true]);
+    }
+
+    #[test]
+    fn test_list_for_hall_uses_hall_created_index() {
+        let db = Database::open_in_memory().unwrap();
+
+        let mut stmt = db
+            .conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", LIST_BEFORE_QUERY))
+            .unwrap();
+        let plan: Vec<String> = stmt
+            .query_map(params!["hall", "2024-01-01T00:00:00+00:00", 50], |row| {
+                row.get(3)
+            })
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        let plan = plan.join("\n");
+        assert!(
+            plan.contains("USING INDEX idx_messages_hall_created"),
+            "{}",
+            plan
+        );
+    }
+
+    #[test]
+    fn test_pages_do_not_overlap() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let start = Utc::now() - chrono::Duration::hours(1);
+        for i in 0..5 {
+            let mut message = Message::new(hall_id, user_id, format!("message {}", i));
+            message.created_at = start + chrono::Duration::seconds(i);
+            db.messages().create(&message).unwrap();
+        }
+
+        let newest = db.messages().list_for_hall(hall_id, 3, None).unwrap();
+        let older = db
+            .messages()
+            .list_for_hall(hall_id, 3, Some(newest[0].timestamp))
+            .unwrap();
+
+        let contents: Vec<_> = older
+            .iter()
+            .chain(&newest)
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "message 0",
+                "message 1",
+                "message 2",
+                "message 3",
+                "message 4"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slow_mode_blocks_rapid_messages() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+        let messages = db.messages();
+
+        // Nothing posted yet
+        messages
+            .can_send_now(hall_id, user_id, HallRole::HallFellow, 30)
+            .unwrap();
+
+        let mut recent = Message::new(hall_id, user_id, "first".into());
+        recent.created_at = Utc::now() - chrono::Duration::seconds(10);
+        messages.create(&recent).unwrap();
+
+        match messages.can_send_now(hall_id, user_id, HallRole::HallFellow, 30) {
+            Err(Error::SlowMode { retry_after }) => assert!((19..=20).contains(&retry_after)),
+            other => panic!("expected slow mode, got {:?}", other),
+        }
+
+        // Moderators are exempt, and a shorter interval has already passed
+        messages
+            .can_send_now(hall_id, user_id, HallRole::HallModerator, 30)
+            .unwrap();
+        messages
+            .can_send_now(hall_id, user_id, HallRole::HallFellow, 5)
+            .unwrap();
+        messages
+            .can_send_now(hall_id, user_id, HallRole::HallFellow, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reply_and_thread() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let root = Message::new(hall_id, user_id, "Who is on call?".into());
+        db.messages().create(&root).unwrap();
+        let first = Message::new(hall_id, user_id, "Me".into()).with_reply_to(root.id);
+        db.messages().create(&first).unwrap();
+        let second = Message::new(hall_id, user_id, "Me too".into()).with_reply_to(root.id);
+        db.messages().create(&second).unwrap();
+        let nested = Message::new(hall_id, user_id, "Thanks".into()).with_reply_to(first.id);
+        db.messages().create(&nested).unwrap();
+
+        assert_eq!(
+            db.messages()
+                .find_by_id(first.id)
+                .unwrap()
+                .unwrap()
+                .reply_to,
+            Some(root.id)
+        );
+
+        let thread = db.messages().list_thread(root.id).unwrap();
+        let ids: Vec<Uuid> = thread.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![root.id, first.id, second.id]);
+        assert_eq!(thread[0].reply_to, None);
+        assert_eq!(thread[1].reply_to, Some(root.id));
+
+        assert!(db
+            .messages()
+            .list_thread(Uuid::new_v4())
+            .unwrap()
+            .is_empty());
+
+        // Purging a deleted root detaches its replies instead of failing
+        db.messages().delete(root.id).unwrap();
+        db.messages()
+            .purge_deleted_older_than(Utc::now() + chrono::Duration::seconds(1))
+            .unwrap();
+        assert_eq!(
+            db.messages()
+                .find_by_id(first.id)
+                .unwrap()
+                .unwrap()
+                .reply_to,
+            None
+        );
+    }
+
+    #[test]
+    fn test_reply_must_target_same_hall() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+        let other_hall = Hall::new("Other Hall".into(), user_id);
+        db.halls().create(&other_hall).unwrap();
+
+        let elsewhere = Message::new(other_hall.id, user_id, "hello".into());
+        db.messages().create(&elsewhere).unwrap();
+
+        let reply = Message::new(hall_id, user_id, "hi".into()).with_reply_to(elsewhere.id);
+        assert!(matches!(
+            db.messages().create(&reply),
+            Err(Error::InvalidOperation(_))
+        ));
+
+        let dangling = Message::new(hall_id, user_id, "hi".into()).with_reply_to(Uuid::new_v4());
+        assert!(matches!(
+            db.messages().create(&dangling),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_create_many_resolves_replies_within_the_batch() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+
+        let parent = Message::new(hall_id, user_id, "Who is on call?".into());
+        let child = Message::new(hall_id, user_id, "Me".into()).with_reply_to(parent.id);
+        let dangling =
+            Message::new(hall_id, user_id, "Agreed".into()).with_reply_to(Uuid::new_v4());
+
+        // The parent arrives after its reply
+        let batch = [child.clone(), parent.clone(), dangling.clone()];
+        assert_eq!(db.messages().create_many(&batch).unwrap(), 3);
+
+        let find = |id| db.messages().find_by_id(id).unwrap().unwrap();
+        assert_eq!(find(child.id).reply_to, Some(parent.id));
+        assert_eq!(find(dangling.id).reply_to, None);
+    }
+
+    #[test]
+    fn test_unread_count_tracks_read_marker() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+        let bob = User::new("bob".into(), "hash".into());
+        db.users().create(&bob).unwrap();
+
+        for i in 0..3 {
+            db.messages()
+                .create(&Message::new(hall_id, bob.id, format!("message {}", i)))
+                .unwrap();
+        }
+        // Own messages are never unread
+        db.messages()
+            .create(&Message::new(hall_id, user_id, "mine".into()))
+            .unwrap();
+        assert_eq!(db.messages().unread_count(user_id, hall_id).unwrap(), 3);
+
+        let latest = db.messages().latest_sequence(hall_id).unwrap();
+        db.messages().mark_read(user_id, hall_id, latest).unwrap();
+        assert_eq!(db.messages().unread_count(user_id, hall_id).unwrap(), 0);
+
+        db.messages()
+            .create(&Message::new(hall_id, bob.id, "another".into()))
+            .unwrap();
+        assert_eq!(db.messages().unread_count(user_id, hall_id).unwrap(), 1);
+
+        // Marking an older sequence does not move the marker back
+        db.messages().mark_read(user_id, hall_id, 0).unwrap();
+        assert_eq!(db.messages().unread_count(user_id, hall_id).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_unread_count_survives_purging_newest_message() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+        let bob = User::new("bob".into(), "hash".into());
+        db.users().create(&bob).unwrap();
+
+        let mut newest = None;
+        for i in 0..3 {
+            let message = Message::new(hall_id, bob.id, format!("message {}", i));
+            db.messages().create(&message).unwrap();
+            newest = Some(message.id);
+        }
+        let latest = db.messages().latest_sequence(hall_id).unwrap();
+        db.messages().mark_read(user_id, hall_id, latest).unwrap();
+
+        db.messages().delete(newest.unwrap()).unwrap();
+        db.messages()
+            .purge_deleted_older_than(Utc::now() + chrono::Duration::seconds(1))
+            .unwrap();
+
+        db.messages()
+            .create(&Message::new(hall_id, bob.id, "after purge".into()))
+            .unwrap();
+        assert_eq!(db.messages().unread_count(user_id, hall_id).unwrap(), 1);
+        assert_eq!(db.messages().latest_sequence(hall_id).unwrap(), latest + 1);
+    }
+
+    #[test]
+    fn test_backfilled_history_counts_as_unread() {
+        let db = Database::open_in_memory().unwrap();
+        let (user_id, hall_id) = setup(&db);
+        let bob = User::new("bob".into(), "hash".into());
+        db.users().create(&bob).unwrap();
+
+        db.messages()
+            .create(&Message::new(hall_id, bob.id, "recent".into()))
+            .unwrap();
+        let latest = db.messages().latest_sequence(hall_id).unwrap();
+        db.messages().mark_read(user_id, hall_id, latest).unwrap();
+
+        let mut old = Message::new(hall_id, bob.id, "from last week".into());
+        old.created_at = Utc::now() - chrono::Duration::days(7);
+        assert_eq!(db.messages().create_many(&[old]).unwrap(), 1);
+
+        // Unread follows arrival order, not the sender's clock
+        assert_eq!(db.messages().unread_count(user_id, hall_id).unwrap(), 1);
+    }
 }
@@ -5,7 +5,7 @@
 use rusqlite::Connection;
 use tracing::{info, instrument};
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 /// A database migration
 pub struct Migration {
@@ -120,18 +120,341 @@ const MIGRATIONS: &[Migration] = &[
             CREATE INDEX IF NOT EXISTS idx_invites_hall ON invites(hall_id);
         "#,
     },
+    Migration {
+        version: 3,
+        description: "Add full-text search index over messages",
+        sql: r#"
+            -- External-content FTS table keyed on the messages rowid
+            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+                content,
+                content = 'messages',
+                content_rowid = 'rowid',
+                tokenize = 'porter unicode61'
+            );
+
+            -- Keep the index in sync with the messages table
+            CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+                INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+                INSERT INTO messages_fts (messages_fts, rowid, content)
+                VALUES ('delete', old.rowid, old.content);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+                INSERT INTO messages_fts (messages_fts, rowid, content)
+                VALUES ('delete', old.rowid, old.content);
+                INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+            END;
+
+            -- Index rows that existed before this migration
+            INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');
+        "#,
+    },
+    Migration {
+        version: 4,
+        description: "Add message reactions",
+        sql: r#"
+            -- Reactions table
+            CREATE TABLE IF NOT EXISTS reactions (
+                message_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                emoji TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                UNIQUE(message_id, user_id, emoji)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_reactions_message ON reactions(message_id);
+        "#,
+    },
+    Migration {
+        version: 5,
+        description: "Add pinned messages",
+        sql: r#"
+            ALTER TABLE messages ADD COLUMN is_pinned INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE messages ADD COLUMN pinned_at TEXT;
+
+            CREATE INDEX IF NOT EXISTS idx_messages_hall_pinned ON messages(hall_id, is_pinned);
+        "#,
+    },
+    Migration {
+        version: 6,
+        description: "Track soft-delete time for message retention",
+        sql: r#"
+            ALTER TABLE messages ADD COLUMN deleted_at TEXT;
+
+            -- Backfill rows deleted before this column existed
+            UPDATE messages SET deleted_at = COALESCE(edited_at, created_at)
+            WHERE is_deleted = 1;
+
+            CREATE INDEX IF NOT EXISTS idx_messages_deleted ON messages(is_deleted, deleted_at);
+        "#,
+    },
+    Migration {
+        version: 7,
+        description: "Add login attempt tracking for account lockout",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS login_attempts (
+                user_id TEXT PRIMARY KEY,
+                failed_count INTEGER NOT NULL DEFAULT 0,
+                first_failed_at TEXT NOT NULL,
+                locked_until TEXT,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            );
+        "#,
+    },
+    Migration {
+        version: 8,
+        description: "Mark bot accounts",
+        sql: r#"
+            ALTER TABLE users ADD COLUMN is_bot INTEGER NOT NULL DEFAULT 0;
+        "#,
+    },
+    Migration {
+        version: 9,
+        description: "Add temporary role grants",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS temporary_roles (
+                user_id TEXT NOT NULL,
+                hall_id TEXT NOT NULL,
+                role INTEGER NOT NULL,
+                expires_at TEXT NOT NULL,
+                FOREIGN KEY (user_id, hall_id) REFERENCES memberships(user_id, hall_id) ON DELETE CASCADE,
+                UNIQUE(user_id, hall_id)
+            );
+        "#,
+    },
+    Migration {
+        version: 10,
+        description: "Add hall bans",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS hall_bans (
+                hall_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                banned_by TEXT NOT NULL,
+                reason TEXT,
+                banned_at TEXT NOT NULL,
+                FOREIGN KEY (hall_id) REFERENCES halls(id) ON DELETE CASCADE,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (banned_by) REFERENCES users(id),
+                UNIQUE(hall_id, user_id)
+            );
+        "#,
+    },
+    Migration {
+        version: 11,
+        description: "Add hall settings",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS hall_settings (
+                hall_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value_json TEXT NOT NULL,
+                FOREIGN KEY (hall_id) REFERENCES halls(id) ON DELETE CASCADE,
+                PRIMARY KEY (hall_id, key)
+            );
+        "#,
+    },
+    Migration {
+        version: 12,
+        description: "Add moderation log",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS moderation_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                hall_id TEXT NOT NULL,
+                actor_id TEXT NOT NULL,
+                target_id TEXT NOT NULL,
+                action TEXT NOT NULL,
+                reason TEXT,
+                at TEXT NOT NULL,
+                FOREIGN KEY (hall_id) REFERENCES halls(id) ON DELETE CASCADE,
+                FOREIGN KEY (actor_id) REFERENCES users(id),
+                FOREIGN KEY (target_id) REFERENCES users(id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_moderation_log_hall ON moderation_log(hall_id, at);
+        "#,
+    },
+    Migration {
+        version: 13,
+        description: "Add message replies",
+        sql: r#"
+            ALTER TABLE messages ADD COLUMN reply_to TEXT REFERENCES messages(id) ON DELETE SET NULL;
+
+            CREATE INDEX IF NOT EXISTS idx_messages_reply_to ON messages(reply_to);
+        "#,
+    },
+    Migration {
+        version: 14,
+        description: "Add read markers",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS read_markers (
+                user_id TEXT NOT NULL,
+                hall_id TEXT NOT NULL,
+                last_read_sequence INTEGER NOT NULL,
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+                FOREIGN KEY (hall_id) REFERENCES halls(id) ON DELETE CASCADE,
+                PRIMARY KEY (user_id, hall_id)
+            );
+        "#,
+    },
+    Migration {
+        version: 15,
+        description: "Add user accent colors",
+        sql: r#"
+            ALTER TABLE users ADD COLUMN accent_color TEXT;
+        "#,
+    },
+    Migration {
+        version: 16,
+        description: "Add per-hall message sequences",
+        sql: r#"
+            ALTER TABLE messages ADD COLUMN sequence INTEGER;
+
+            CREATE TABLE IF NOT EXISTS hall_sequences (
+                hall_id TEXT PRIMARY KEY,
+                last_sequence INTEGER NOT NULL,
+                FOREIGN KEY (hall_id) REFERENCES halls(id) ON DELETE CASCADE
+            );
+
+            -- Number existing messages in the order they were sent
+            UPDATE messages SET sequence = numbered.seq
+            FROM (
+                SELECT rowid AS rid,
+                       ROW_NUMBER() OVER (PARTITION BY hall_id ORDER BY created_at, rowid) AS seq
+                FROM messages
+            ) AS numbered
+            WHERE messages.rowid = numbered.rid;
+
+            INSERT INTO hall_sequences (hall_id, last_sequence)
+            SELECT hall_id, MAX(sequence) FROM messages GROUP BY hall_id;
+
+            -- Sequences only ever grow, even when the newest message is purged
+            CREATE TRIGGER IF NOT EXISTS messages_assign_sequence AFTER INSERT ON messages BEGIN
+                INSERT INTO hall_sequences (hall_id, last_sequence) VALUES (new.hall_id, 1)
+                ON CONFLICT(hall_id) DO UPDATE SET last_sequence = last_sequence + 1;
+                UPDATE messages
+                SET sequence = (SELECT last_sequence FROM hall_sequences WHERE hall_id = new.hall_id)
+                WHERE rowid = new.rowid;
+            END;
+
+            CREATE INDEX IF NOT EXISTS idx_messages_hall_sequence ON messages(hall_id, sequence);
+
+            -- Read markers held rowids until now; convert them to sequences
+            ALTER TABLE read_markers ADD COLUMN last_read_at TEXT;
+
+            UPDATE read_markers SET last_read_sequence = COALESCE(
+                (SELECT MAX(m.sequence) FROM messages m
+                 WHERE m.hall_id = read_markers.hall_id
+                   AND m.rowid <= read_markers.last_read_sequence),
+                0);
+
+            UPDATE read_markers SET last_read_at = (
+                SELECT MAX(m.created_at) FROM messages m
+                WHERE m.hall_id = read_markers.hall_id
+                  AND m.sequence <= read_markers.last_read_sequence);
+        "#,
+    },
+    Migration {
+        version: 17,
+        description: "Move bot accounts into the reserved namespace",
+        sql: r#"
+            UPDATE users SET username = 'bot:' || username
+            WHERE is_bot = 1
+              AND username NOT LIKE 'bot:%'
+              AND NOT EXISTS (SELECT 1 FROM users u WHERE u.username = 'bot:' || users.username);
+        "#,
+    },
+    Migration {
+        version: 18,
+        description: "Drop read marker timestamps",
+        sql: r#"
+            ALTER TABLE read_markers DROP COLUMN last_read_at;
+        "#,
+    },
 ];
 
+impl Migration {
+    /// Stable checksum of the migration SQL (64-bit FNV-1a, hex encoded)
+    ///
+    /// Recorded when the migration is applied, so an edited migration is
+    /// caught instead of silently diverging from existing databases.
+    fn checksum(&self) -> String {
+        let hash = self
+            .sql
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+        format!("{:016x}", hash)
+    }
+}
+
 /// Initialize the migrations table
 fn init_migrations_table(conn: &Connection) -> Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS schema_migrations (
             version INTEGER PRIMARY KEY,
             description TEXT NOT NULL,
-            applied_at TEXT NOT NULL
+            applied_at TEXT NOT NULL,
+            checksum TEXT
         )",
         [],
     )?;
+
+    // Databases created before checksums were recorded lack the column
+    let has_checksum: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('schema_migrations') WHERE name = 'checksum')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_checksum {
+        conn.execute("ALTER TABLE schema_migrations ADD COLUMN checksum TEXT", [])?;
+    }
+    Ok(())
+}
+
+/// Check applied migrations against the ones this binary knows
+///
+/// Rejects databases from a newer app version and migrations whose SQL has
+/// changed since they were applied. Rows recorded before checksums existed
+/// are adopted with the current checksum.
+fn verify_applied(conn: &Connection, current_version: u32) -> Result<()> {
+    let supported = MIGRATIONS.last().map(|m| m.version).unwrap_or(0);
+    if current_version > supported {
+        return Err(Error::SchemaTooNew {
+            found: current_version,
+            supported,
+        });
+    }
+
+    let mut stmt = conn.prepare("SELECT version, checksum FROM schema_migrations")?;
+    let applied = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for (version, checksum) in applied {
+        let Some(migration) = MIGRATIONS.iter().find(|m| m.version == version) else {
+            continue;
+        };
+        match checksum {
+            Some(checksum) if checksum != migration.checksum() => {
+                return Err(Error::MigrationChecksumMismatch { version });
+            }
+            Some(_) => {}
+            None => {
+                conn.execute(
+                    "UPDATE schema_migrations SET checksum = ?1 WHERE version = ?2",
+                    rusqlite::params![migration.checksum(), version],
+                )?;
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -148,23 +471,28 @@ fn get_current_version(conn: &Connection) -> Result<u32> {
 /// Record that a migration was applied
 fn record_migration(conn: &Connection, migration: &Migration) -> Result<()> {
     conn.execute(
-        "INSERT INTO schema_migrations (version, description, applied_at) VALUES (?1, ?2, ?3)",
+        "INSERT INTO schema_migrations (version, description, applied_at, checksum) VALUES (?1, ?2, ?3, ?4)",
         rusqlite::params![
             migration.version,
             migration.description,
-            chrono::Utc::now().to_rfc3339()
+            chrono::Utc::now().to_rfc3339(),
+            migration.checksum()
         ],
     )?;
     Ok(())
 }
 
 /// Run all pending migrations
+///
+/// Each migration runs in its own transaction together with its
+/// `schema_migrations` record, so a crash never leaves one half-applied.
 #[instrument(skip(conn))]
 pub fn run_migrations(conn: &Connection) -> Result<()> {
     init_migrations_table(conn)?;
 
     let current_version = get_current_version(conn)?;
     info!(current_version, "Checking for pending migrations");
+    verify_applied(conn, current_version)?;
 
     for migration in MIGRATIONS {
         if migration.version > current_version {
@@ -174,8 +502,10 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
                 "Applying migration"
             );
 
-            conn.execute_batch(migration.sql)?;
-            record_migration(conn, migration)?;
+            let tx = conn.unchecked_transaction()?;
+            tx.execute_batch(migration.sql)?;
+            record_migration(&tx, migration)?;
+            tx.commit()?;
 
             info!(version = migration.version, "Migration complete");
         }
@@ -236,4 +566,75 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_checksum_mismatch_detected() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        conn.execute(
+            "UPDATE schema_migrations SET checksum = 'edited' WHERE version = 2",
+            [],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            run_migrations(&conn),
+            Err(Error::MigrationChecksumMismatch { version: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_missing_checksums_are_adopted() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        conn.execute("UPDATE schema_migrations SET checksum = NULL", [])
+            .unwrap();
+        run_migrations(&conn).unwrap();
+
+        let missing: u32 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM schema_migrations WHERE checksum IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(missing, 0);
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        let future = latest_version() + 1;
+        conn.execute(
+            "INSERT INTO schema_migrations (version, description, applied_at) VALUES (?1, 'future', '')",
+            [future],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            run_migrations(&conn),
+            Err(Error::SchemaTooNew { found, .. }) if found == future
+        ));
+    }
+
+    #[test]
+    fn test_legacy_migrations_table_gains_checksum() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE schema_migrations (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+        assert_eq!(get_current_version(&conn).unwrap(), latest_version());
+    }
 }
@@ -1,10 +1,12 @@
 //! SQLite storage layer for Exom
 
+mod export;
 mod halls;
 mod invites;
+mod maintenance;
 mod messages;
 mod migrations;
-mod parse;
+pub(crate) mod parse;
 mod traits;
 mod users;
 
@@ -13,17 +15,42 @@ use uuid::Uuid;
 
 use crate::error::Result;
 use crate::models::{
-    Hall, HallRole, Invite, MemberInfo, Membership, Message, MessageDisplay, Session, User,
+    Hall, HallRole, Invite, MemberInfo, Membership, Message, MessageDisplay, ModerationAction,
+    ModerationLogEntry, Session, User,
 };
-use rusqlite::Connection;
+use rusqlite::{Connection, Transaction};
 use std::path::Path;
 use tracing::instrument;
 
+pub use export::{ExportedUser, HallExport};
 pub use halls::HallStore;
 pub use invites::InviteStore;
+pub use maintenance::MaintenanceReport;
 pub use messages::MessageStore;
 pub use traits::{HallRepository, InviteRepository, MessageRepository, Storage, UserRepository};
-pub use users::UserStore;
+pub use users::{HashParams, UserStore};
+
+/// Connection options for opening a database
+#[derive(Debug, Clone, Copy)]
+pub struct DbOptions {
+    /// Use write-ahead logging (`PRAGMA journal_mode = WAL`)
+    ///
+    /// WAL lets readers proceed while a write is in progress, but creates
+    /// `-wal` and `-shm` sidecar files next to the database file. It has
+    /// no effect on in-memory databases.
+    pub wal: bool,
+    /// How long to wait on a locked database before failing, in milliseconds
+    pub busy_timeout_ms: u32,
+}
+
+impl Default for DbOptions {
+    fn default() -> Self {
+        Self {
+            wal: true,
+            busy_timeout_ms: 5000,
+        }
+    }
+}
 
 /// Main database handle
 pub struct Database {
@@ -31,11 +58,16 @@ pub struct Database {
 }
 
 impl Database {
+    /// Open or create database at the given path with default options
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_options(path, DbOptions::default())
+    }
+
     /// Open or create database at the given path
     #[instrument(skip(path), fields(path = %path.as_ref().display()))]
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    pub fn open_with_options<P: AsRef<Path>>(path: P, options: DbOptions) -> Result<Self> {
         let conn = Connection::open(path)?;
-        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        Self::configure(&conn, options)?;
         let db = Self { conn };
         db.init()?;
         Ok(db)
@@ -45,18 +77,55 @@ impl Database {
     #[instrument]
     pub fn open_in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()?;
-        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        Self::configure(
+            &conn,
+            DbOptions {
+                wal: false,
+                ..DbOptions::default()
+            },
+        )?;
         let db = Self { conn };
         db.init()?;
         Ok(db)
     }
 
+    /// Apply connection pragmas
+    fn configure(conn: &Connection, options: DbOptions) -> Result<()> {
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        conn.busy_timeout(std::time::Duration::from_millis(
+            options.busy_timeout_ms as u64,
+        ))?;
+        if options.wal {
+            let mode: String =
+                conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+            if !mode.eq_ignore_ascii_case("wal") {
+                tracing::warn!(mode, "WAL journal mode not available");
+            }
+        }
+        Ok(())
+    }
+
     /// Initialize database schema via migrations
     fn init(&self) -> Result<()> {
         migrations::run_migrations(&self.conn)?;
         Ok(())
     }
 
+    /// Run `f` inside a transaction, committing only if it succeeds
+    ///
+    /// Stores can be built on the transaction directly (e.g.
+    /// `HallStore::new(tx)`), so several store operations either all apply
+    /// or none do.
+    pub fn transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T>,
+    {
+        let tx = self.conn.unchecked_transaction()?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
+    }
+
     /// Get current schema version
     pub fn schema_version(&self) -> u32 {
         self.conn
@@ -161,6 +230,10 @@ impl HallRepository for Database {
         self.halls().update_role(user_id, hall_id, new_role)
     }
 
+    fn step_down(&self, user_id: Uuid, hall_id: Uuid, new_role: HallRole) -> Result<()> {
+        self.halls().step_down(user_id, hall_id, new_role)
+    }
+
     fn update_online_status(&self, user_id: Uuid, hall_id: Uuid, is_online: bool) -> Result<()> {
         self.halls()
             .update_online_status(user_id, hall_id, is_online)
@@ -174,9 +247,77 @@ impl HallRepository for Database {
         self.halls().list_members(hall_id)
     }
 
+    fn member_count(&self, hall_id: Uuid) -> Result<u64> {
+        self.halls().member_count(hall_id)
+    }
+
+    fn online_count(&self, hall_id: Uuid) -> Result<u64> {
+        self.halls().online_count(hall_id)
+    }
+
     fn get_user_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<HallRole>> {
         self.halls().get_user_role(user_id, hall_id)
     }
+
+    fn base_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<HallRole>> {
+        self.halls().base_role(user_id, hall_id)
+    }
+
+    fn grant_temporary_role(
+        &self,
+        user_id: Uuid,
+        hall_id: Uuid,
+        role: HallRole,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.halls()
+            .grant_temporary_role(user_id, hall_id, role, expires_at)
+    }
+
+    fn ban_user(
+        &self,
+        hall_id: Uuid,
+        user_id: Uuid,
+        banned_by: Uuid,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.halls().ban_user(hall_id, user_id, banned_by, reason)
+    }
+
+    fn kick_member(
+        &self,
+        hall_id: Uuid,
+        user_id: Uuid,
+        kicked_by: Uuid,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.halls()
+            .kick_member(hall_id, user_id, kicked_by, reason)
+    }
+
+    fn log_moderation(
+        &self,
+        hall_id: Uuid,
+        actor_id: Uuid,
+        target_id: Uuid,
+        action: ModerationAction,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        self.halls()
+            .log_moderation(hall_id, actor_id, target_id, action, reason)
+    }
+
+    fn list_moderation_log(&self, hall_id: Uuid, limit: u32) -> Result<Vec<ModerationLogEntry>> {
+        self.halls().list_moderation_log(hall_id, limit)
+    }
+
+    fn unban_user(&self, hall_id: Uuid, user_id: Uuid) -> Result<()> {
+        self.halls().unban_user(hall_id, user_id)
+    }
+
+    fn is_banned(&self, hall_id: Uuid, user_id: Uuid) -> Result<bool> {
+        self.halls().is_banned(hall_id, user_id)
+    }
 }
 
 impl MessageRepository for Database {
@@ -184,6 +325,10 @@ impl MessageRepository for Database {
         self.messages().create(message)
     }
 
+    fn create_messages(&self, messages: &[Message]) -> Result<usize> {
+        self.messages().create_many(messages)
+    }
+
     fn find_message_by_id(&self, id: Uuid) -> Result<Option<Message>> {
         self.messages().find_by_id(id)
     }
@@ -197,6 +342,19 @@ impl MessageRepository for Database {
         self.messages().list_for_hall(hall_id, limit, before)
     }
 
+    fn list_messages_between(
+        &self,
+        hall_id: Uuid,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<MessageDisplay>> {
+        self.messages().list_for_hall_between(hall_id, start, end)
+    }
+
+    fn list_message_thread(&self, root_id: Uuid) -> Result<Vec<MessageDisplay>> {
+        self.messages().list_thread(root_id)
+    }
+
     fn update_message_content(&self, message_id: Uuid, new_content: &str) -> Result<()> {
         self.messages().update_content(message_id, new_content)
     }
@@ -205,9 +363,50 @@ impl MessageRepository for Database {
         self.messages().delete(message_id)
     }
 
+    fn purge_deleted_messages(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        self.messages().purge_deleted_older_than(cutoff)
+    }
+
     fn count_messages_for_hall(&self, hall_id: Uuid) -> Result<u64> {
         self.messages().count_for_hall(hall_id)
     }
+
+    fn mark_read(&self, user_id: Uuid, hall_id: Uuid, sequence: u64) -> Result<()> {
+        self.messages().mark_read(user_id, hall_id, sequence)
+    }
+
+    fn unread_count(&self, user_id: Uuid, hall_id: Uuid) -> Result<u64> {
+        self.messages().unread_count(user_id, hall_id)
+    }
+
+    fn set_message_pinned(&self, message_id: Uuid, pinned: bool) -> Result<()> {
+        self.messages().set_pinned(message_id, pinned)
+    }
+
+    fn list_pinned_messages(&self, hall_id: Uuid) -> Result<Vec<MessageDisplay>> {
+        self.messages().list_pinned(hall_id)
+    }
+
+    fn add_reaction(&self, message_id: Uuid, user_id: Uuid, emoji: &str) -> Result<()> {
+        self.messages().add_reaction(message_id, user_id, emoji)
+    }
+
+    fn remove_reaction(&self, message_id: Uuid, user_id: Uuid, emoji: &str) -> Result<()> {
+        self.messages().remove_reaction(message_id, user_id, emoji)
+    }
+
+    fn list_reactions(&self, message_id: Uuid) -> Result<Vec<(String, u32)>> {
+        self.messages().list_reactions(message_id)
+    }
+
+    fn search_messages(
+        &self,
+        hall_id: Uuid,
+        query: &str,
+        limit: u32,
+    ) -> Result<Vec<MessageDisplay>> {
+        self.messages().search(hall_id, query, limit)
+    }
 }
 
 impl InviteRepository for Database {
@@ -223,6 +422,10 @@ impl InviteRepository for Database {
         self.invites().list_for_hall(hall_id)
     }
 
+    fn redeem_invite(&self, token: &str) -> Result<Invite> {
+        self.invites().redeem(token)
+    }
+
     fn increment_use_count(&self, invite_id: Uuid) -> Result<()> {
         self.invites().increment_use_count(invite_id)
     }
@@ -235,3 +438,96 @@ impl InviteRepository for Database {
         self.invites().delete(invite_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_transaction_rolls_back_on_error() {
+        let db = Database::open_in_memory().unwrap();
+        let owner = User::new("owner".into(), "hash".into());
+        db.users().create(&owner).unwrap();
+
+        let hall = Hall::new("Atomic Hall".into(), owner.id);
+        let hall_id = hall.id;
+
+        // Membership for a user that does not exist fails the foreign key
+        let result = db.transaction(|tx| {
+            let halls = HallStore::new(tx);
+            halls.create(&hall)?;
+            halls.add_member(&Membership::new(
+                Uuid::new_v4(),
+                hall_id,
+                HallRole::HallBuilder,
+            ))
+        });
+
+        assert!(result.is_err());
+        assert!(db.halls().find_by_id(hall_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_transaction_commits_on_success() {
+        let db = Database::open_in_memory().unwrap();
+        let owner = User::new("owner".into(), "hash".into());
+        db.users().create(&owner).unwrap();
+
+        let hall = Hall::new("Atomic Hall".into(), owner.id);
+        let hall_id = hall.id;
+
+        db.transaction(|tx| {
+            let halls = HallStore::new(tx);
+            halls.create(&hall)?;
+            halls.add_member(&Membership::new(owner.id, hall_id, HallRole::HallBuilder))
+        })
+        .unwrap();
+
+        assert!(db.halls().find_by_id(hall_id).unwrap().is_some());
+        assert_eq!(
+            db.halls().get_user_role(owner.id, hall_id).unwrap(),
+            Some(HallRole::HallBuilder)
+        );
+    }
+
+    #[test]
+    fn test_open_enables_wal_and_busy_timeout() {
+        let dir = tempdir().unwrap();
+        let db = Database::open(dir.path().join("exom.db")).unwrap();
+
+        let mode: String = db
+            .conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode.to_lowercase(), "wal");
+
+        let timeout: i64 = db
+            .conn
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(timeout, 5000);
+    }
+
+    #[test]
+    fn test_open_with_wal_disabled() {
+        let dir = tempdir().unwrap();
+        let options = DbOptions {
+            wal: false,
+            busy_timeout_ms: 250,
+        };
+        let db = Database::open_with_options(dir.path().join("exom.db"), options).unwrap();
+
+        let mode: String = db
+            .conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode.to_lowercase(), "delete");
+
+        let timeout: i64 = db
+            .conn
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(timeout, 250);
+    }
+}
@@ -8,7 +8,8 @@ use uuid::Uuid;
 
 use crate::error::Result;
 use crate::models::{
-    Hall, HallRole, Invite, MemberInfo, Membership, Message, MessageDisplay, Session, User,
+    Hall, HallRole, Invite, MemberInfo, Membership, Message, MessageDisplay, ModerationAction,
+    ModerationLogEntry, Session, User,
 };
 
 /// User repository operations
@@ -64,9 +65,12 @@ pub trait HallRepository {
     /// Get a membership
     fn get_membership(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<Membership>>;
 
-    /// Update membership role
+    /// Update a member's base role, revoking any temporary grant
     fn update_role(&self, user_id: Uuid, hall_id: Uuid, new_role: HallRole) -> Result<()>;
 
+    /// Lower a member's own role, checked against their base role
+    fn step_down(&self, user_id: Uuid, hall_id: Uuid, new_role: HallRole) -> Result<()>;
+
     /// Update online status
     fn update_online_status(&self, user_id: Uuid, hall_id: Uuid, is_online: bool) -> Result<()>;
 
@@ -76,8 +80,63 @@ pub trait HallRepository {
     /// List members of a Hall with user info
     fn list_members(&self, hall_id: Uuid) -> Result<Vec<MemberInfo>>;
 
-    /// Get user's role in a Hall
+    /// Count members of a Hall
+    fn member_count(&self, hall_id: Uuid) -> Result<u64>;
+
+    /// Count members of a Hall currently online
+    fn online_count(&self, hall_id: Uuid) -> Result<u64>;
+
+    /// Get user's effective role in a Hall, including temporary grants
     fn get_user_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<HallRole>>;
+
+    /// Get a member's stored role, ignoring temporary grants
+    fn base_role(&self, user_id: Uuid, hall_id: Uuid) -> Result<Option<HallRole>>;
+
+    /// Temporarily raise a member's role until `expires_at`
+    fn grant_temporary_role(
+        &self,
+        user_id: Uuid,
+        hall_id: Uuid,
+        role: HallRole,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()>;
+
+    /// Ban a user from a Hall, removing their membership
+    fn ban_user(
+        &self,
+        hall_id: Uuid,
+        user_id: Uuid,
+        banned_by: Uuid,
+        reason: Option<&str>,
+    ) -> Result<()>;
+
+    /// Remove a member on a moderator's behalf and record it
+    fn kick_member(
+        &self,
+        hall_id: Uuid,
+        user_id: Uuid,
+        kicked_by: Uuid,
+        reason: Option<&str>,
+    ) -> Result<()>;
+
+    /// Append an entry to a Hall's moderation log
+    fn log_moderation(
+        &self,
+        hall_id: Uuid,
+        actor_id: Uuid,
+        target_id: Uuid,
+        action: ModerationAction,
+        reason: Option<&str>,
+    ) -> Result<()>;
+
+    /// List the most recent moderation log entries for a Hall
+    fn list_moderation_log(&self, hall_id: Uuid, limit: u32) -> Result<Vec<ModerationLogEntry>>;
+
+    /// Lift a ban so the user can rejoin
+    fn unban_user(&self, hall_id: Uuid, user_id: Uuid) -> Result<()>;
+
+    /// Check if a user is banned from a Hall
+    fn is_banned(&self, hall_id: Uuid, user_id: Uuid) -> Result<bool>;
 }
 
 /// Message repository operations
@@ -85,6 +144,9 @@ pub trait MessageRepository {
     /// Create a new message
     fn create_message(&self, message: &Message) -> Result<()>;
 
+    /// Insert a batch of messages atomically, skipping existing ids
+    fn create_messages(&self, messages: &[Message]) -> Result<usize>;
+
     /// Find message by ID
     fn find_message_by_id(&self, id: Uuid) -> Result<Option<Message>>;
 
@@ -96,14 +158,57 @@ pub trait MessageRepository {
         before: Option<DateTime<Utc>>,
     ) -> Result<Vec<MessageDisplay>>;
 
+    /// List messages for a Hall sent within `[start, end)`, oldest first
+    fn list_messages_between(
+        &self,
+        hall_id: Uuid,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<MessageDisplay>>;
+
+    /// List a message followed by its direct replies
+    fn list_message_thread(&self, root_id: Uuid) -> Result<Vec<MessageDisplay>>;
+
     /// Update message content
     fn update_message_content(&self, message_id: Uuid, new_content: &str) -> Result<()>;
 
     /// Soft delete a message
     fn delete_message(&self, message_id: Uuid) -> Result<()>;
 
+    /// Permanently remove messages soft-deleted before the cutoff
+    fn purge_deleted_messages(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+
     /// Get message count for a Hall
     fn count_messages_for_hall(&self, hall_id: Uuid) -> Result<u64>;
+
+    /// Record that a user has read a Hall up to `sequence`
+    fn mark_read(&self, user_id: Uuid, hall_id: Uuid, sequence: u64) -> Result<()>;
+
+    /// Count messages in a Hall the user has not read yet
+    fn unread_count(&self, user_id: Uuid, hall_id: Uuid) -> Result<u64>;
+
+    /// Pin or unpin a message
+    fn set_message_pinned(&self, message_id: Uuid, pinned: bool) -> Result<()>;
+
+    /// List pinned messages for a Hall in pin order
+    fn list_pinned_messages(&self, hall_id: Uuid) -> Result<Vec<MessageDisplay>>;
+
+    /// Add a reaction to a message
+    fn add_reaction(&self, message_id: Uuid, user_id: Uuid, emoji: &str) -> Result<()>;
+
+    /// Remove a reaction from a message
+    fn remove_reaction(&self, message_id: Uuid, user_id: Uuid, emoji: &str) -> Result<()>;
+
+    /// List reactions on a message as (emoji, count)
+    fn list_reactions(&self, message_id: Uuid) -> Result<Vec<(String, u32)>>;
+
+    /// Full-text search over message content in a Hall
+    fn search_messages(
+        &self,
+        hall_id: Uuid,
+        query: &str,
+        limit: u32,
+    ) -> Result<Vec<MessageDisplay>>;
 }
 
 /// Invite repository operations
@@ -117,6 +222,9 @@ pub trait InviteRepository {
     /// List invites for a Hall
     fn list_invites_for_hall(&self, hall_id: Uuid) -> Result<Vec<Invite>>;
 
+    /// Redeem an invite, atomically consuming one use
+    fn redeem_invite(&self, token: &str) -> Result<Invite>;
+
     /// Increment invite use count
     fn increment_use_count(&self, invite_id: Uuid) -> Result<()>;
 
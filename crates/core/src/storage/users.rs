@@ -1,13 +1,83 @@
 //! User storage operations
 
-use chrono::Utc;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use chrono::{DateTime, Duration, Utc};
 use rusqlite::{params, Connection};
-use tracing::instrument;
+use tracing::{info, instrument};
 use uuid::Uuid;
 
 use super::parse::{parse_datetime, parse_datetime_opt, parse_uuid, OptionalExt};
-use crate::error::Result;
-use crate::models::{Session, User};
+use crate::error::{Error, Result};
+use crate::models::{bot_username, is_hex_color, is_reserved_username, Session, User};
+
+/// Failed logins within the window that trigger a lockout
+pub const MAX_FAILED_LOGINS: u32 = 5;
+
+/// Window in which failed logins are counted, in minutes
+pub const FAILED_LOGIN_WINDOW_MINUTES: i64 = 15;
+
+/// How long an account stays locked, in minutes
+pub const LOCKOUT_MINUTES: i64 = 15;
+
+/// Argon2id cost parameters used when hashing passwords
+///
+/// Stored hashes weaker than these are upgraded on the next successful
+/// login by `UserStore::verify_and_maybe_rehash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashParams {
+    /// Memory cost in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl HashParams {
+    /// Hash a password with these parameters
+    pub fn hash_password(&self, password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2()?
+            .hash_password(password.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(Error::from)
+    }
+
+    /// Whether a stored hash was made with weaker parameters than these
+    fn is_weaker(&self, hash: &PasswordHash<'_>) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+
+        match Params::try_from(hash) {
+            Ok(stored) => {
+                stored.m_cost() < self.m_cost
+                    || stored.t_cost() < self.t_cost
+                    || stored.p_cost() < self.p_cost
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, None)
+            .map_err(|e| Error::InvalidOperation(format!("Invalid hash parameters: {}", e)))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for HashParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
 
 pub struct UserStore<'a> {
     conn: &'a Connection,
@@ -21,14 +91,23 @@ impl<'a> UserStore<'a> {
     /// Create a new user
     #[instrument(skip(self, user), fields(username = %user.username))]
     pub fn create(&self, user: &User) -> Result<()> {
+        if !user.is_bot && is_reserved_username(&user.username) {
+            return Err(Error::InvalidOperation(format!(
+                "Username {} is reserved for bots",
+                user.username
+            )));
+        }
+
         self.conn.execute(
-            "INSERT INTO users (id, username, password_hash, created_at, last_login) VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO users (id, username, password_hash, created_at, last_login, is_bot, accent_color) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 user.id.to_string(),
                 user.username,
                 user.password_hash,
                 user.created_at.to_rfc3339(),
                 user.last_login.map(|t| t.to_rfc3339()),
+                user.is_bot as i32,
+                user.accent_color,
             ],
         )?;
         Ok(())
@@ -38,7 +117,7 @@ impl<'a> UserStore<'a> {
     #[instrument(skip(self))]
     pub fn find_by_id(&self, id: Uuid) -> Result<Option<User>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, username, password_hash, created_at, last_login FROM users WHERE id = ?1",
+            "SELECT id, username, password_hash, created_at, last_login, is_bot, accent_color FROM users WHERE id = ?1",
         )?;
 
         let user = stmt
@@ -49,6 +128,8 @@ impl<'a> UserStore<'a> {
                     password_hash: row.get(2)?,
                     created_at: parse_datetime(&row.get::<_, String>(3)?)?,
                     last_login: parse_datetime_opt(row.get::<_, Option<String>>(4)?)?,
+                    is_bot: row.get::<_, i32>(5)? != 0,
+                    accent_color: row.get(6)?,
                 })
             })
             .optional()?;
@@ -60,7 +141,7 @@ impl<'a> UserStore<'a> {
     #[instrument(skip(self))]
     pub fn find_by_username(&self, username: &str) -> Result<Option<User>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, username, password_hash, created_at, last_login FROM users WHERE username = ?1",
+            "SELECT id, username, password_hash, created_at, last_login, is_bot, accent_color FROM users WHERE username = ?1",
         )?;
 
         let user = stmt
@@ -71,6 +152,8 @@ impl<'a> UserStore<'a> {
                     password_hash: row.get(2)?,
                     created_at: parse_datetime(&row.get::<_, String>(3)?)?,
                     last_login: parse_datetime_opt(row.get::<_, Option<String>>(4)?)?,
+                    is_bot: row.get::<_, i32>(5)? != 0,
+                    accent_color: row.get(6)?,
                 })
             })
             .optional()?;
@@ -78,6 +161,50 @@ impl<'a> UserStore<'a> {
         Ok(user)
     }
 
+    /// Get or create the reserved account a bot sends messages as
+    ///
+    /// The account's username is [`bot_username`] of `bot_id`, which people
+    /// can't register, and it has no password, so it can never log in.
+    #[instrument(skip(self))]
+    pub fn ensure_bot_user(&self, bot_id: &str) -> Result<User> {
+        let username = bot_username(bot_id);
+        if let Some(user) = self.find_by_username(&username)? {
+            if !user.is_bot {
+                return Err(Error::InvalidOperation(format!(
+                    "Username {} belongs to a user, not a bot",
+                    username
+                )));
+            }
+            return Ok(user);
+        }
+
+        let user = User {
+            is_bot: true,
+            ..User::new(username, String::new())
+        };
+        self.create(&user)?;
+        Ok(user)
+    }
+
+    /// Set or clear a user's accent color (`#rrggbb`)
+    #[instrument(skip(self))]
+    pub fn set_accent_color(&self, user_id: Uuid, color: Option<&str>) -> Result<()> {
+        if let Some(color) = color {
+            if !is_hex_color(color) {
+                return Err(Error::InvalidOperation(format!(
+                    "Invalid accent color: {}",
+                    color
+                )));
+            }
+        }
+
+        self.conn.execute(
+            "UPDATE users SET accent_color = ?1 WHERE id = ?2",
+            params![color, user_id.to_string()],
+        )?;
+        Ok(())
+    }
+
     /// Update last login time
     pub fn update_last_login(&self, user_id: Uuid) -> Result<()> {
         self.conn.execute(
@@ -87,6 +214,142 @@ impl<'a> UserStore<'a> {
         Ok(())
     }
 
+    /// Replace a user's password hash
+    #[instrument(skip(self, password_hash))]
+    pub fn update_password_hash(&self, user_id: Uuid, password_hash: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE users SET password_hash = ?1 WHERE id = ?2",
+            params![password_hash, user_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Verify a password, upgrading the stored hash if it is outdated
+    ///
+    /// Returns `None` if the user does not exist or the password is wrong.
+    /// On success, a hash made with weaker parameters than `current_params`
+    /// is transparently replaced.
+    #[instrument(skip(self, password, current_params))]
+    pub fn verify_and_maybe_rehash(
+        &self,
+        username: &str,
+        password: &str,
+        current_params: &HashParams,
+    ) -> Result<Option<User>> {
+        let mut user = match self.find_by_username(username)? {
+            Some(u) => u,
+            None => return Ok(None),
+        };
+
+        // Bot accounts and imported accounts without a password never log in
+        if user.is_bot || user.password_hash.is_empty() {
+            return Ok(None);
+        }
+
+        let parsed_hash = PasswordHash::new(&user.password_hash)?;
+
+        if Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_err()
+        {
+            return Ok(None);
+        }
+
+        if current_params.is_weaker(&parsed_hash) {
+            let new_hash = current_params.hash_password(password)?;
+            self.update_password_hash(user.id, &new_hash)?;
+            info!(user_id = %user.id, "Upgraded password hash parameters");
+            user.password_hash = new_hash;
+        }
+
+        Ok(Some(user))
+    }
+
+    /// Record a failed login attempt for a username
+    ///
+    /// Returns the lockout expiry if this attempt locked the account.
+    /// Unknown usernames are ignored.
+    #[instrument(skip(self))]
+    pub fn record_failed_login(&self, username: &str) -> Result<Option<DateTime<Utc>>> {
+        let user = match self.find_by_username(username)? {
+            Some(u) => u,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now();
+        let existing = self
+            .conn
+            .query_row(
+                "SELECT failed_count, first_failed_at FROM login_attempts WHERE user_id = ?1",
+                params![user.id.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, u32>(0)?,
+                        parse_datetime(&row.get::<_, String>(1)?)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        // Start a fresh window if there is none or the last one has lapsed
+        let (failed_count, first_failed_at) = match existing {
+            Some((count, first))
+                if now - first < Duration::minutes(FAILED_LOGIN_WINDOW_MINUTES) =>
+            {
+                (count + 1, first)
+            }
+            _ => (1, now),
+        };
+
+        let locked_until =
+            (failed_count >= MAX_FAILED_LOGINS).then(|| now + Duration::minutes(LOCKOUT_MINUTES));
+
+        self.conn.execute(
+            "INSERT INTO login_attempts (user_id, failed_count, first_failed_at, locked_until)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(user_id) DO UPDATE SET
+                failed_count = excluded.failed_count,
+                first_failed_at = excluded.first_failed_at,
+                locked_until = excluded.locked_until",
+            params![
+                user.id.to_string(),
+                failed_count,
+                first_failed_at.to_rfc3339(),
+                locked_until.map(|t| t.to_rfc3339()),
+            ],
+        )?;
+
+        Ok(locked_until)
+    }
+
+    /// Clear failed login tracking after a successful login
+    #[instrument(skip(self))]
+    pub fn clear_failed_logins(&self, user_id: Uuid) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM login_attempts WHERE user_id = ?1",
+            params![user_id.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Check whether an account is locked, returning when the lock expires
+    #[instrument(skip(self))]
+    pub fn is_locked(&self, username: &str) -> Result<Option<DateTime<Utc>>> {
+        let locked_until = self
+            .conn
+            .query_row(
+                "SELECT a.locked_until FROM login_attempts a
+                 INNER JOIN users u ON u.id = a.user_id
+                 WHERE u.username = ?1",
+                params![username],
+                |row| parse_datetime_opt(row.get::<_, Option<String>>(0)?),
+            )
+            .optional()?
+            .flatten();
+
+        Ok(locked_until.filter(|until| *until > Utc::now()))
+    }
+
     /// Create a session
     #[instrument(skip(self, session), fields(user_id = %session.user_id))]
     pub fn create_session(&self, session: &Session) -> Result<()> {
@@ -151,3 +414,206 @@ impl<'a> UserStore<'a> {
         Ok(count as u64)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Database;
+
+    const LEGACY: HashParams = HashParams {
+        m_cost: 1024,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    const CURRENT: HashParams = HashParams {
+        m_cost: 2048,
+        t_cost: 2,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn test_legacy_hash_upgraded_on_login() {
+        let db = Database::open_in_memory().unwrap();
+        let legacy_hash = LEGACY.hash_password("hunter22").unwrap();
+        let user = User::new("alice".into(), legacy_hash.clone());
+        db.users().create(&user).unwrap();
+
+        let verified = db
+            .users()
+            .verify_and_maybe_rehash("alice", "hunter22", &CURRENT)
+            .unwrap()
+            .unwrap();
+        assert_ne!(verified.password_hash, legacy_hash);
+
+        let stored = db.users().find_by_id(user.id).unwrap().unwrap();
+        assert_eq!(stored.password_hash, verified.password_hash);
+        let parsed = PasswordHash::new(&stored.password_hash).unwrap();
+        assert!(!CURRENT.is_weaker(&parsed));
+
+        // The upgraded hash still verifies and is left alone
+        let again = db
+            .users()
+            .verify_and_maybe_rehash("alice", "hunter22", &CURRENT)
+            .unwrap()
+            .unwrap();
+        assert_eq!(again.password_hash, stored.password_hash);
+    }
+
+    #[test]
+    fn test_wrong_password_does_not_rehash() {
+        let db = Database::open_in_memory().unwrap();
+        let legacy_hash = LEGACY.hash_password("hunter22").unwrap();
+        let user = User::new("alice".into(), legacy_hash.clone());
+        db.users().create(&user).unwrap();
+
+        let result = db
+            .users()
+            .verify_and_maybe_rehash("alice", "wrong", &CURRENT)
+            .unwrap();
+        assert!(result.is_none());
+
+        let stored = db.users().find_by_id(user.id).unwrap().unwrap();
+        assert_eq!(stored.password_hash, legacy_hash);
+
+        assert!(db
+            .users()
+            .verify_and_maybe_rehash("nobody", "hunter22", &CURRENT)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_fifth_failed_login_locks_account() {
+        let db = Database::open_in_memory().unwrap();
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+
+        for _ in 0..MAX_FAILED_LOGINS - 1 {
+            assert!(db.users().record_failed_login("alice").unwrap().is_none());
+            assert!(db.users().is_locked("alice").unwrap().is_none());
+        }
+
+        let locked_until = db.users().record_failed_login("alice").unwrap();
+        assert!(locked_until.is_some());
+        assert_eq!(db.users().is_locked("alice").unwrap(), locked_until);
+
+        // Unknown users are never locked
+        assert!(db.users().record_failed_login("nobody").unwrap().is_none());
+        assert!(db.users().is_locked("nobody").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_successful_login_clears_counter() {
+        let db = Database::open_in_memory().unwrap();
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+
+        for _ in 0..MAX_FAILED_LOGINS - 1 {
+            db.users().record_failed_login("alice").unwrap();
+        }
+        db.users().clear_failed_logins(user.id).unwrap();
+
+        // Counter restarted, so one more failure does not lock
+        assert!(db.users().record_failed_login("alice").unwrap().is_none());
+        assert!(db.users().is_locked("alice").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_lock_expires() {
+        let db = Database::open_in_memory().unwrap();
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            db.users().record_failed_login("alice").unwrap();
+        }
+        assert!(db.users().is_locked("alice").unwrap().is_some());
+
+        let past = (Utc::now() - Duration::minutes(1)).to_rfc3339();
+        db.conn
+            .execute("UPDATE login_attempts SET locked_until = ?1", params![past])
+            .unwrap();
+        assert!(db.users().is_locked("alice").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_ensure_bot_user_is_stable() {
+        let db = Database::open_in_memory().unwrap();
+
+        let first = db.users().ensure_bot_user("archivist").unwrap();
+        let second = db.users().ensure_bot_user("archivist").unwrap();
+        assert_eq!(first.id, second.id);
+        assert!(second.is_bot);
+        assert_eq!(second.username, "bot:archivist");
+    }
+
+    #[test]
+    fn test_bot_username_cannot_be_squatted() {
+        let db = Database::open_in_memory().unwrap();
+
+        // The bot's username is reserved before the bot ever posts
+        assert!(matches!(
+            db.users()
+                .create(&User::new("bot:crier".into(), "hash".into())),
+            Err(Error::InvalidOperation(_))
+        ));
+
+        // A person sharing the bot's id keeps their own account
+        let person = User::new("crier".into(), "hash".into());
+        db.users().create(&person).unwrap();
+        let bot = db.users().ensure_bot_user("crier").unwrap();
+        assert_ne!(bot.id, person.id);
+        assert!(bot.is_bot);
+    }
+
+    #[test]
+    fn test_bot_accounts_cannot_log_in() {
+        let db = Database::open_in_memory().unwrap();
+        db.users().ensure_bot_user("archivist").unwrap();
+
+        for password in ["", "anything"] {
+            assert!(db
+                .users()
+                .verify_and_maybe_rehash("bot:archivist", password, &CURRENT)
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_corrupt_stored_hash_is_a_hash_error() {
+        let db = Database::open_in_memory().unwrap();
+        db.users()
+            .create(&User::new("alice".into(), "not a phc string".into()))
+            .unwrap();
+
+        assert!(matches!(
+            db.users()
+                .verify_and_maybe_rehash("alice", "secret", &CURRENT),
+            Err(Error::PasswordHash(_))
+        ));
+    }
+
+    #[test]
+    fn test_accent_color_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let user = User::new("alice".into(), "hash".into());
+        db.users().create(&user).unwrap();
+
+        db.users()
+            .set_accent_color(user.id, Some("#336699"))
+            .unwrap();
+        let stored = db.users().find_by_id(user.id).unwrap().unwrap();
+        assert_eq!(stored.display_color(), "#336699");
+
+        assert!(matches!(
+            db.users().set_accent_color(user.id, Some("blue")),
+            Err(Error::InvalidOperation(_))
+        ));
+
+        db.users().set_accent_color(user.id, None).unwrap();
+        let stored = db.users().find_by_id(user.id).unwrap().unwrap();
+        assert_eq!(stored.display_color(), user.display_color());
+    }
+}